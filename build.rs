@@ -0,0 +1,44 @@
+// With the "ffi" feature: generates the C header of src/ffi.rs into OUT_DIR (include/merkletree.h
+// is a copy of it). With "ffi-test" it also compiles the C test program of the ffi tests, so only
+// the tests need a C compiler.
+fn main() {
+    #[cfg(feature = "ffi")]
+    ffi();
+    #[cfg(feature = "ffi-test")]
+    ffi_test();
+}
+
+#[cfg(feature = "ffi")]
+fn ffi() {
+    use std::path::PathBuf;
+
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/ffi.rs"))
+        .generate()
+        .expect("Unable to generate the C header")
+        .write_to_file(out_dir.join("merkletree.h"));
+}
+
+#[cfg(feature = "ffi-test")]
+fn ffi_test() {
+    use std::path::PathBuf;
+
+    let crate_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=tests/ffi/test_ffi.c");
+
+    // Only linked by the tests of src/ffi.rs
+    cc::Build::new()
+        .file(crate_dir.join("tests/ffi/test_ffi.c"))
+        .include(&out_dir)
+        .cargo_metadata(false)
+        .compile("ffi_test");
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
+}
@@ -0,0 +1,2 @@
+// Only re-exports the C ABI, the functions are defined in the ffi module of merkletree_in_rust.
+pub use merkletree_in_rust::ffi::*;
@@ -0,0 +1,82 @@
+#ifndef MERKLETREE_H
+#define MERKLETREE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum MtStatus {
+  MT_STATUS_OK = 0,
+  MT_STATUS_NULL_POINTER,
+  MT_STATUS_INVALID_UTF8,
+  MT_STATUS_EMPTY_TREE,
+  MT_STATUS_UNKNOWN_HASHER,
+  MT_STATUS_INDEX_OUT_OF_BOUNDS,
+  MT_STATUS_BUFFER_TOO_SMALL,
+  MT_STATUS_MALFORMED_PROOF,
+} MtStatus;
+
+typedef struct MtTree MtTree;
+
+// Builds a tree from `count` leaves, leaf `i` being the `leaf_lens[i]` bytes (UTF-8) at `leaves[i]`.
+// `hasher` is the name of a hash function of the hashers module, or NULL for keccak256.
+//
+// # Safety
+// `leaves` and `leaf_lens` point to `count` elements, each leaf to `leaf_lens[i]` readable bytes,
+// `hasher` is NULL or a NUL-terminated string and `tree` is writable.
+enum MtStatus mt_tree_new(const uint8_t *const *leaves,
+                          const size_t *leaf_lens,
+                          size_t count,
+                          const char *hasher,
+                          struct MtTree **tree);
+
+// Releases a tree of mt_tree_new. NULL is ignored.
+//
+// # Safety
+// `tree` is NULL or a tree of mt_tree_new that was not freed yet.
+void mt_tree_free(struct MtTree *tree);
+
+// Number of leaves of the tree, 0 for NULL.
+//
+// # Safety
+// `tree` is NULL or a live tree of mt_tree_new.
+size_t mt_tree_leaves_count(const struct MtTree *tree);
+
+// Writes the hex root and a NUL into `root` (`root_len` bytes), its length into `written`.
+//
+// # Safety
+// `tree` is a live tree of mt_tree_new, `root` has `root_len` writable bytes and `written` is
+// writable.
+enum MtStatus mt_tree_root(const struct MtTree *tree, char *root, size_t root_len, size_t *written);
+
+// Writes the proof of the leaf at `index` and a NUL into `proof` (`proof_len` bytes), its length
+// into `written`. The proof is the concatenation of the hex hashes of the siblings, bottom-up.
+//
+// # Safety
+// `tree` is a live tree of mt_tree_new, `proof` has `proof_len` writable bytes and `written` is
+// writable.
+enum MtStatus mt_tree_proof(const struct MtTree *tree,
+                            size_t index,
+                            char *proof,
+                            size_t proof_len,
+                            size_t *written);
+
+// Checks that the leaf (`leaf_len` bytes at `leaf`) is at `index` in the tree of `root`, with a
+// proof of mt_tree_proof. `valid` is only written when MT_STATUS_OK is returned.
+//
+// # Safety
+// `root` and `proof` are NUL-terminated strings, `leaf` has `leaf_len` readable bytes, `hasher`
+// is NULL or a NUL-terminated string and `valid` is writable.
+enum MtStatus mt_verify_proof(const char *root,
+                              const uint8_t *leaf,
+                              size_t leaf_len,
+                              size_t index,
+                              const char *proof,
+                              const char *hasher,
+                              bool *valid);
+
+#endif  /* MERKLETREE_H */
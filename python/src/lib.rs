@@ -0,0 +1,231 @@
+// Python bindings of the trees and proofs of merkletree_in_rust, so roots and proofs computed in
+// Python are the ones of the crate and of its command-line tool:
+//
+//   tree = merkletree.MerkleTree(leaves, hasher="keccak256", odd="duplicate", encoding="hex")
+//   proof = tree.proof(tree.index_of("E"))
+//   proof.verify(root=tree.root)
+//
+// With odd="duplicate" (the default), the roots and proofs are the ones of MerkleTreeVec.
+// Errors of the crate (unknown hasher, index out of bounds...) are raised as ValueError.
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use merkletree_in_rust::hashers;
+use merkletree_in_rust::kary_merkle_tree::{kary_proof_from_levels, FillPolicy, KaryMerkleTree};
+use merkletree_in_rust::proof::{Encoding, Proof};
+
+fn value_error(e: String) -> PyErr {
+    PyValueError::new_err(e)
+}
+
+fn odd_name(odd: FillPolicy) -> &'static str {
+    match odd {
+        FillPolicy::DuplicateLast => "duplicate",
+        FillPolicy::Truncate => "truncate",
+    }
+}
+
+fn encoding_name(encoding: Encoding) -> &'static str {
+    match encoding {
+        Encoding::Hex => "hex",
+        Encoding::PrefixedHex => "0x",
+        Encoding::Base64 => "base64",
+    }
+}
+
+#[pyclass(name = "MerkleTree", frozen)]
+struct PyMerkleTree {
+    leaves: Vec<String>,
+    // Encoded
+    root: String,
+    // Hashed once in new(), proofs are read from them
+    levels: Vec<Vec<String>>,
+    hasher: String,
+    odd: FillPolicy,
+    encoding: Encoding,
+}
+
+#[pyclass(name = "Proof", frozen)]
+struct PyProof {
+    proof: Proof,
+}
+
+#[pymethods]
+impl PyMerkleTree {
+    #[new]
+    #[pyo3(signature = (leaves, hasher = "keccak256", odd = "duplicate", encoding = "hex"))]
+    fn new(leaves: Vec<String>, hasher: &str, odd: &str, encoding: &str) -> PyResult<Self> {
+        let hash_fn = hashers::by_name(hasher).map_err(value_error)?;
+        let odd = FillPolicy::from_name(odd).map_err(value_error)?;
+        let encoding = Encoding::from_name(encoding).map_err(value_error)?;
+        let tree = KaryMerkleTree::new(&leaves, 2, odd, &hash_fn).map_err(value_error)?;
+        let root = encoding.encode(&tree.root).map_err(value_error)?;
+        let levels = tree.levels();
+
+        Ok(PyMerkleTree {
+            leaves,
+            root,
+            levels,
+            hasher: hasher.to_string(),
+            odd,
+            encoding,
+        })
+    }
+
+    #[getter]
+    fn root(&self) -> &str {
+        &self.root
+    }
+
+    #[getter]
+    fn leaves(&self) -> Vec<String> {
+        self.leaves.clone()
+    }
+
+    #[getter]
+    fn hasher(&self) -> &str {
+        &self.hasher
+    }
+
+    #[getter]
+    fn odd(&self) -> &'static str {
+        odd_name(self.odd)
+    }
+
+    #[getter]
+    fn encoding(&self) -> &'static str {
+        encoding_name(self.encoding)
+    }
+
+    fn __len__(&self) -> usize {
+        self.leaves.len()
+    }
+
+    // Index of the first occurrence of the leaf
+    fn index_of(&self, leaf: &str) -> PyResult<usize> {
+        self.leaves
+            .iter()
+            .position(|x| x == leaf)
+            .ok_or_else(|| value_error("Item not found".to_string()))
+    }
+
+    fn proof(&self, index: usize) -> PyResult<PyProof> {
+        let kary_proof =
+            kary_proof_from_levels(&self.levels, index, 2, self.odd).map_err(value_error)?;
+        // The last level only holds the root
+        let root = &self.levels[self.levels.len() - 1][0];
+        let proof = Proof::encoded(
+            &self.leaves[index],
+            root,
+            kary_proof,
+            &self.hasher,
+            self.odd,
+            2,
+            self.encoding,
+        )
+        .map_err(value_error)?;
+        Ok(PyProof { proof })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MerkleTree(root={:?}, leaves={}, hasher={:?}, odd={:?})",
+            self.root,
+            self.leaves.len(),
+            self.hasher,
+            odd_name(self.odd)
+        )
+    }
+}
+
+#[pymethods]
+impl PyProof {
+    #[getter]
+    fn leaf(&self) -> &str {
+        &self.proof.leaf
+    }
+
+    #[getter]
+    fn index(&self) -> usize {
+        self.proof.index
+    }
+
+    #[getter]
+    fn root(&self) -> &str {
+        &self.proof.root
+    }
+
+    #[getter]
+    fn siblings(&self) -> Vec<Vec<String>> {
+        self.proof.siblings.clone()
+    }
+
+    #[getter]
+    fn hasher(&self) -> &str {
+        &self.proof.hasher
+    }
+
+    #[getter]
+    fn odd(&self) -> &'static str {
+        odd_name(self.proof.odd)
+    }
+
+    #[getter]
+    fn encoding(&self) -> &'static str {
+        encoding_name(self.proof.encoding)
+    }
+
+    // Checks the proof against its own root and, if given, that this root is the trusted one
+    // (written with the encoding of the proof).
+    #[pyo3(signature = (root = None))]
+    fn verify(&self, root: Option<&str>) -> PyResult<bool> {
+        let encoding = self.proof.encoding;
+        let trusted = match root {
+            Some(root) => {
+                let trusted_root = encoding.decode(root).map_err(value_error)?;
+                let root = encoding.decode(&self.proof.root).map_err(value_error)?;
+                hashers::constant_time_eq(root.as_bytes(), trusted_root.as_bytes())
+            }
+            None => true,
+        };
+        // The proof is checked even for another root, the time doesn't tell which check failed
+        Ok(self.proof.verify().map_err(value_error)? & trusted)
+    }
+
+    fn to_json(&self) -> String {
+        self.proof.to_json()
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<PyProof> {
+        let proof = Proof::from_json(json).map_err(value_error)?;
+        Ok(PyProof { proof })
+    }
+
+    fn __eq__(&self, other: &PyProof) -> bool {
+        self.proof == other.proof
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Proof(leaf={:?}, index={}, root={:?})",
+            self.proof.leaf, self.proof.index, self.proof.root
+        )
+    }
+}
+
+// The hash function, as used for the leaves and the nodes
+#[pyfunction]
+#[pyo3(signature = (value, hasher = "keccak256"))]
+fn hash(value: &str, hasher: &str) -> PyResult<String> {
+    let hash_fn = hashers::by_name(hasher).map_err(value_error)?;
+    Ok(hash_fn(value))
+}
+
+#[pymodule]
+fn merkletree(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMerkleTree>()?;
+    m.add_class::<PyProof>()?;
+    m.add_function(wrap_pyfunction!(hash, m)?)?;
+    Ok(())
+}
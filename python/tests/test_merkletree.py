@@ -0,0 +1,84 @@
+# Tests of the Python bindings, run with pytest after `maturin develop` (see the README).
+# The expected roots are the ones printed by the command-line tool for the same leaves.
+import pytest
+
+import merkletree
+
+LEAVES = ["D", "E", "F", "G", "H"]
+
+# cargo run -- root leaves.txt
+KECCAK_ROOT = "e292bbde8e532949a367ebdf77bd8042f779f6c8eebcc0ea0deff00a7798f479"
+# cargo run -- root --odd truncate leaves.txt
+KECCAK_TRUNCATE_ROOT = "1174c10027e95fdcbc2dcf6989dad9fab07615af21783e959a951e2e8bb98dde"
+# cargo run --features sha256 -- root --hasher sha256 --encoding 0x leaves.txt
+SHA256_ROOT = "0x3a2d911cf034f2ef19a954e546f717072287920f5643993c972e3f912a898fea"
+
+
+def test_root():
+    tree = merkletree.MerkleTree(LEAVES)
+    assert tree.root == KECCAK_ROOT
+    assert len(tree) == 5
+    assert tree.leaves == LEAVES
+    assert (tree.hasher, tree.odd, tree.encoding) == ("keccak256", "duplicate", "hex")
+
+    assert merkletree.MerkleTree(LEAVES, odd="truncate").root == KECCAK_TRUNCATE_ROOT
+    assert merkletree.MerkleTree(LEAVES, hasher="sha256", encoding="0x").root == SHA256_ROOT
+
+
+def test_single_leaf():
+    # Paired with itself
+    tree = merkletree.MerkleTree(["D"])
+    leaf = merkletree.hash("D")
+    assert tree.root == merkletree.hash(leaf + leaf)
+
+
+def test_proofs():
+    for odd in ["duplicate", "truncate"]:
+        tree = merkletree.MerkleTree(LEAVES, odd=odd, encoding="base64")
+        for index, leaf in enumerate(LEAVES):
+            proof = tree.proof(index)
+            assert (proof.leaf, proof.index, proof.root) == (leaf, index, tree.root)
+            assert proof.odd == odd
+            assert proof.verify()
+            assert proof.verify(root=tree.root)
+
+        other = merkletree.MerkleTree(LEAVES[:4], odd=odd, encoding="base64")
+        assert not tree.proof(0).verify(root=other.root)
+
+
+def test_proofs_of_large_tree():
+    # The levels are hashed once, each proof only reads them
+    leaves = [str(i) for i in range(1000)]
+    tree = merkletree.MerkleTree(leaves)
+    for index in range(0, 1000, 7):
+        proof = tree.proof(index)
+        assert len(proof.siblings) == 10
+        assert proof.verify(root=tree.root)
+
+
+def test_tampered_proof():
+    tree = merkletree.MerkleTree(LEAVES)
+    proof = tree.proof(tree.index_of("F"))
+    assert proof.index == 2
+    assert len(proof.siblings) == 3
+
+    json = proof.to_json()
+    assert merkletree.Proof.from_json(json) == proof
+    tampered = merkletree.Proof.from_json(json.replace('"leaf": "F"', '"leaf": "X"'))
+    assert not tampered.verify()
+
+
+def test_errors():
+    with pytest.raises(ValueError, match="Empty tree"):
+        merkletree.MerkleTree([])
+    with pytest.raises(ValueError, match="Unknown hasher"):
+        merkletree.MerkleTree(LEAVES, hasher="md5")
+    with pytest.raises(ValueError, match="Unknown odd-node policy"):
+        merkletree.MerkleTree(LEAVES, odd="zero")
+    tree = merkletree.MerkleTree(LEAVES)
+    with pytest.raises(ValueError, match="Index out of bounds"):
+        tree.proof(5)
+    with pytest.raises(ValueError, match="Item not found"):
+        tree.index_of("X")
+    with pytest.raises(ValueError):
+        merkletree.Proof.from_json("{}")
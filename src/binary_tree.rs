@@ -1,25 +1,138 @@
 // Tutorial -> https://www.youtube.com/watch?v=yHi3q2Iiepc
-use std::fmt;
-type Leaf = Option<Box<Node>>;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Bound, RangeBounds, RangeFull};
 
-struct Tree {
-    root: Leaf,
+use crate::hashers::constant_time_eq;
+
+type Leaf<K, V> = Option<Box<Node<K, V>>>;
+
+// Hash of a node from its key, its value and the hashes of its children
+type NodeHasher<K, V> = dyn Fn(&K, &V, &str, &str) -> String;
+
+// Ordered map: every key of the left subtree is smaller than the key of the node,
+// every key of the right subtree is bigger.
+pub struct BinarySearchTree<K: Ord, V> {
+    root: Leaf<K, V>,
+    len: usize,
+    balancing: Balancing,
+    // Set for authenticated trees, see BinarySearchTree::authenticated
+    hash_fn: Option<fn(&str) -> String>,
+    hasher: Option<Box<NodeHasher<K, V>>>,
 }
 
-struct Node {
-    item: i32,
-    left: Leaf,
-    right: Leaf,
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Balancing {
+    // Sorted input turns the tree into a linked list
+    #[default]
+    None,
+    // The heights of the two subtrees of every node differ by at most one, so the height
+    // stays O(log n). Insertions and removals rotate the nodes on their path when needed.
+    Avl,
 }
 
-impl Node {
-    fn new(item: i32) -> Self {
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // Only kept up to date with Balancing::Avl
+    height: usize,
+    // Only for authenticated trees, an empty subtree hashes to ""
+    hash: String,
+    left: Leaf<K, V>,
+    right: Leaf<K, V>,
+}
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V) -> Self {
         Node {
-            item,
+            key,
+            value,
+            height: 1,
+            hash: String::new(),
             left: None,
             right: None,
         }
     }
+
+    fn update(&mut self, hasher: Option<&NodeHasher<K, V>>) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+        if let Some(hasher) = hasher {
+            self.hash = hasher(
+                &self.key,
+                &self.value,
+                hash_of(&self.left),
+                hash_of(&self.right),
+            );
+        }
+    }
+}
+
+fn height<K, V>(link: &Leaf<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn hash_of<K, V>(link: &Leaf<K, V>) -> &str {
+    link.as_ref().map_or("", |node| node.hash.as_str())
+}
+
+//      node           left
+//      /  \           /  \
+//   left   c   ->    a   node
+//   /  \                 /  \
+//  a    b               b    c
+fn rotate_right<K, V>(
+    mut node: Box<Node<K, V>>,
+    hasher: Option<&NodeHasher<K, V>>,
+) -> Box<Node<K, V>> {
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    node.update(hasher);
+    left.right = Some(node);
+    left.update(hasher);
+    left
+}
+
+fn rotate_left<K, V>(
+    mut node: Box<Node<K, V>>,
+    hasher: Option<&NodeHasher<K, V>>,
+) -> Box<Node<K, V>> {
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    node.update(hasher);
+    right.left = Some(node);
+    right.update(hasher);
+    right
+}
+
+// Restores the AVL invariant of a node whose subtrees are balanced, and updates its hash
+fn rebalance<K, V>(
+    mut node: Box<Node<K, V>>,
+    hasher: Option<&NodeHasher<K, V>>,
+) -> Box<Node<K, V>> {
+    node.update(hasher);
+    let (left, right) = (height(&node.left), height(&node.right));
+
+    if left > right + 1 {
+        let child = node.left.as_ref().unwrap();
+        // Left-right case: the child leans the other way
+        if height(&child.left) < height(&child.right) {
+            node.left = Some(rotate_left(node.left.take().unwrap(), hasher));
+        }
+        return rotate_right(node, hasher);
+    }
+    if right > left + 1 {
+        let child = node.right.as_ref().unwrap();
+        if height(&child.right) < height(&child.left) {
+            node.right = Some(rotate_right(node.right.take().unwrap(), hasher));
+        }
+        return rotate_left(node, hasher);
+    }
+    node
 }
 
 enum Dir {
@@ -28,11 +141,11 @@ enum Dir {
     Root,
 }
 
-impl fmt::Display for Tree {
+impl<K: Ord + fmt::Display, V> fmt::Display for BinarySearchTree<K, V> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(root) = &self.root {
             writeln!(f, "Tree:")?;
-            Tree::print_node(f, root, 0, Dir::Root)?;
+            BinarySearchTree::print_node(f, root, 0, Dir::Root)?;
         } else {
             write!(f, "Empty Tree")?;
         }
@@ -40,69 +153,447 @@ impl fmt::Display for Tree {
     }
 }
 
-impl From<Node> for Leaf {
-    fn from(node: Node) -> Self {
+impl<K, V> From<Node<K, V>> for Leaf<K, V> {
+    fn from(node: Node<K, V>) -> Self {
         Some(Box::new(node))
     }
 }
 
-impl Tree {
+impl<K: Ord, V> Default for BinarySearchTree<K, V> {
+    fn default() -> Self {
+        BinarySearchTree::new()
+    }
+}
+
+impl<K: Ord, V> BinarySearchTree<K, V> {
     pub fn new() -> Self {
-        Tree { root: None }
+        BinarySearchTree::with_balancing(Balancing::None)
     }
 
-    pub fn insert(&mut self, value: i32) {
-        /* for insert_recursive
-        match &mut self.root {
-            None => self.root = Node::new(value).into(),
-            Some(node) => Tree::insert_recursive(node, value),
+    pub fn with_balancing(balancing: Balancing) -> Self {
+        BinarySearchTree {
+            root: None,
+            len: 0,
+            balancing,
+            hash_fn: None,
+            hasher: None,
         }
-        */
-        self.insert_iterative(value)
     }
 
-    fn insert_recursive(node: &mut Box<Node>, value: i32) {
-        match value.cmp(&node.item) {
-            std::cmp::Ordering::Greater | std::cmp::Ordering::Equal => match &mut node.right {
-                None => node.right = Node::new(value).into(),
-                Some(n) => Tree::insert_recursive(n, value),
-            },
-            std::cmp::Ordering::Less => match &mut node.left {
-                None => node.left = Node::new(value).into(),
-                Some(n) => Tree::insert_recursive(n, value),
-            },
-            // if the value is equal
+    // Number of nodes on the longest path from the root, 0 for an empty tree
+    pub fn height(&self) -> usize {
+        fn subtree_height<K, V>(link: &Leaf<K, V>) -> usize {
+            link.as_ref().map_or(0, |node| {
+                1 + subtree_height(&node.left).max(subtree_height(&node.right))
+            })
+        }
+        subtree_height(&self.root)
+    }
+
+    // None if the tree is not authenticated, "" for an empty one
+    pub fn root_hash(&self) -> Option<String> {
+        self.hash_fn?;
+        Some(
+            self.root
+                .as_ref()
+                .map_or(String::new(), |node| node.hash.clone()),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns the previous value when the key was already there
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.balancing == Balancing::Avl {
+            let mut previous = None;
+            self.root = Some(BinarySearchTree::insert_avl(
+                self.root.take(),
+                key,
+                value,
+                &mut previous,
+                self.hasher.as_deref(),
+            ));
+            if previous.is_none() {
+                self.len += 1;
+            }
+            return previous;
+        }
+        let mut link = &mut self.root;
+
+        while let Some(node) = link {
+            match key.cmp(&node.key) {
+                Ordering::Less => link = &mut node.left,
+                Ordering::Greater => link = &mut node.right,
+                Ordering::Equal => return Some(core::mem::replace(&mut node.value, value)),
+            }
+        }
+        *link = Node::new(key, value).into();
+        self.len += 1;
+        None
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let mut link = &self.root;
+
+        while let Some(node) = link {
+            match key.cmp(&node.key) {
+                Ordering::Less => link = &node.left,
+                Ordering::Greater => link = &node.right,
+                Ordering::Equal => return Some(&node.value),
+            }
+        }
+        None
+    }
+
+    // Fails for authenticated trees, the hashes would go stale: use insert instead.
+    pub fn get_mut(&mut self, key: &K) -> Result<&mut V, String> {
+        if self.hasher.is_some() {
+            return Err("Read-only authenticated tree: use insert".to_string());
+        }
+        let mut link = &mut self.root;
+
+        while let Some(node) = link {
+            match key.cmp(&node.key) {
+                Ordering::Less => link = &mut node.left,
+                Ordering::Greater => link = &mut node.right,
+                Ordering::Equal => return Ok(&mut node.value),
+            }
+        }
+        Err("Item not found".to_string())
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if self.balancing == Balancing::Avl {
+            let (root, removed) =
+                BinarySearchTree::remove_avl(self.root.take(), key, self.hasher.as_deref());
+            self.root = root;
+            if removed.is_some() {
+                self.len -= 1;
+            }
+            return removed;
+        }
+        let mut link = &mut self.root;
+        while link.as_ref().is_some_and(|node| node.key != *key) {
+            let node = link.as_mut().unwrap();
+            link = if *key < node.key {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
         }
+
+        let mut node = link.take()?;
+        *link = match (node.left.take(), node.right.take()) {
+            (None, None) => None,
+            (Some(child), None) | (None, Some(child)) => Some(child),
+            // The smallest key of the right subtree takes the place of the node
+            (Some(left), Some(right)) => {
+                let (mut successor, rest) = BinarySearchTree::take_min(right);
+                successor.left = Some(left);
+                successor.right = rest;
+                Some(successor)
+            }
+        };
+        self.len -= 1;
+        Some(node.value)
     }
 
-    fn insert_iterative(&mut self, value: i32) {
-        if self.root.is_none() {
-            self.root = Node::new(value).into();
-            return;
+    // Detaches the smallest node of the subtree, returns it and what is left of the subtree
+    fn take_min(mut node: Box<Node<K, V>>) -> (Box<Node<K, V>>, Leaf<K, V>) {
+        match node.left.take() {
+            None => {
+                let rest = node.right.take();
+                (node, rest)
+            }
+            Some(left) => {
+                let (min, rest) = BinarySearchTree::take_min(left);
+                node.left = rest;
+                (min, Some(node))
+            }
         }
+    }
 
-        let mut q: Vec<&mut Box<Node>> = Vec::new();
-        let root = self.root.as_mut().unwrap();
-        q.push(root);
+    // The AVL operations are recursive, each node on the path is rebalanced on the way back up
+    fn insert_avl(
+        link: Leaf<K, V>,
+        key: K,
+        value: V,
+        previous: &mut Option<V>,
+        hasher: Option<&NodeHasher<K, V>>,
+    ) -> Box<Node<K, V>> {
+        let mut node = match link {
+            None => return rebalance(Box::new(Node::new(key, value)), hasher),
+            Some(node) => node,
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => {
+                node.left = Some(BinarySearchTree::insert_avl(
+                    node.left.take(),
+                    key,
+                    value,
+                    previous,
+                    hasher,
+                ))
+            }
+            Ordering::Greater => {
+                node.right = Some(BinarySearchTree::insert_avl(
+                    node.right.take(),
+                    key,
+                    value,
+                    previous,
+                    hasher,
+                ))
+            }
+            Ordering::Equal => *previous = Some(core::mem::replace(&mut node.value, value)),
+        }
+        rebalance(node, hasher)
+    }
 
-        while let Some(node) = q.pop() {
-            match value.cmp(&node.item) {
-                std::cmp::Ordering::Greater | std::cmp::Ordering::Equal => match node.right {
-                    ref mut right @ None => *right = Node::new(value).into(),
-                    Some(ref mut n) => q.push(n),
-                },
-                std::cmp::Ordering::Less => match node.left {
-                    ref mut left @ None => *left = Node::new(value).into(),
-                    Some(ref mut n) => q.push(n),
-                },
-                // if the value is equal
+    fn remove_avl(
+        link: Leaf<K, V>,
+        key: &K,
+        hasher: Option<&NodeHasher<K, V>>,
+    ) -> (Leaf<K, V>, Option<V>) {
+        let mut node = match link {
+            None => return (None, None),
+            Some(node) => node,
+        };
+        let removed = match key.cmp(&node.key) {
+            Ordering::Less => {
+                let (left, removed) = BinarySearchTree::remove_avl(node.left.take(), key, hasher);
+                node.left = left;
+                removed
+            }
+            Ordering::Greater => {
+                let (right, removed) = BinarySearchTree::remove_avl(node.right.take(), key, hasher);
+                node.right = right;
+                removed
+            }
+            Ordering::Equal => {
+                let replacement = match (node.left.take(), node.right.take()) {
+                    (None, None) => None,
+                    (Some(child), None) | (None, Some(child)) => Some(child),
+                    (Some(left), Some(right)) => {
+                        let (mut successor, rest) = BinarySearchTree::take_min_avl(right, hasher);
+                        successor.left = Some(left);
+                        successor.right = rest;
+                        Some(rebalance(successor, hasher))
+                    }
+                };
+                return (replacement, Some(node.value));
+            }
+        };
+        (Some(rebalance(node, hasher)), removed)
+    }
+
+    fn take_min_avl(
+        mut node: Box<Node<K, V>>,
+        hasher: Option<&NodeHasher<K, V>>,
+    ) -> (Box<Node<K, V>>, Leaf<K, V>) {
+        match node.left.take() {
+            None => {
+                let rest = node.right.take();
+                (node, rest)
+            }
+            Some(left) => {
+                let (min, rest) = BinarySearchTree::take_min_avl(left, hasher);
+                node.left = rest;
+                (min, Some(rebalance(node, hasher)))
             }
         }
     }
 
+    pub fn min(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some((&node.key, &node.value))
+    }
+
+    // In-order: sorted by key
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.range(..)
+    }
+
+    // In-order, only the keys within the range. Subtrees outside of it are not visited.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, R> {
+        let mut iter = Range {
+            stack: Vec::new(),
+            range,
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn pre_order(&self) -> PreOrder<'_, K, V> {
+        PreOrder {
+            stack: self.root.iter().map(|node| node.as_ref()).collect(),
+        }
+    }
+
+    pub fn post_order(&self) -> PostOrder<'_, K, V> {
+        PostOrder {
+            stack: self
+                .root
+                .iter()
+                .map(|node| (node.as_ref(), false))
+                .collect(),
+        }
+    }
+
+    pub fn level_order(&self) -> LevelOrder<'_, K, V> {
+        LevelOrder {
+            queue: self.root.iter().map(|node| node.as_ref()).collect(),
+        }
+    }
+}
+
+// Node of the search path of a BstProof
+#[derive(Clone, Debug, PartialEq)]
+pub struct BstProofNode<K> {
+    pub key: K,
+    pub value_hash: String,
+    pub left: String,
+    pub right: String,
+}
+
+// The nodes visited when searching a key, from the root. The search ends either on the node of
+// the key (presence) or on an empty child where the key would be (absence).
+#[derive(Clone, Debug, PartialEq)]
+pub struct BstProof<K> {
+    pub path: Vec<BstProofNode<K>>,
+}
+
+// The children hashes are "" for empty subtrees, they are replaced by the hash of "" so every part
+// of the concatenation has the same length.
+pub fn node_hash(
+    hash_fn: &dyn Fn(&str) -> String,
+    key: &str,
+    value_hash: &str,
+    left: &str,
+    right: &str,
+) -> String {
+    let child = |hash: &str| match hash {
+        "" => hash_fn(""),
+        hash => hash.to_string(),
+    };
+    hash_fn(&format!(
+        "{}{}{}{}",
+        hash_fn(key),
+        value_hash,
+        child(left),
+        child(right)
+    ))
+}
+
+impl<K: Ord + fmt::Display + Clone + 'static, V: fmt::Display + 'static> BinarySearchTree<K, V> {
+    // AVL tree where every node also stores a hash over its key, its value and the hashes of its
+    // children, so the root hash commits to the whole sorted dictionary (see get_proof).
+    // Keys and values are hashed through their Display output.
+    pub fn authenticated(hash_fn: fn(&str) -> String) -> Self {
+        let mut tree = BinarySearchTree::with_balancing(Balancing::Avl);
+        tree.hash_fn = Some(hash_fn);
+        tree.hasher = Some(Box::new(
+            move |key: &K, value: &V, left: &str, right: &str| {
+                let value_hash = hash_fn(&value.to_string());
+                node_hash(&hash_fn, &key.to_string(), &value_hash, left, right)
+            },
+        ));
+        tree
+    }
+
+    // Proof of the value of the key, or of its absence
+    pub fn get_proof(&self, key: &K) -> Result<BstProof<K>, String> {
+        let hash_fn = self
+            .hash_fn
+            .ok_or("Not an authenticated tree".to_string())?;
+        let mut path = Vec::new();
+        let mut link = &self.root;
+
+        while let Some(node) = link {
+            path.push(BstProofNode {
+                key: node.key.clone(),
+                value_hash: hash_fn(&node.value.to_string()),
+                left: hash_of(&node.left).to_string(),
+                right: hash_of(&node.right).to_string(),
+            });
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => break,
+            };
+        }
+        Ok(BstProof { path })
+    }
+}
+
+impl<K: Ord + fmt::Display> BstProof<K> {
+    // With Some(value), checks that the key maps to the value. With None, that the key is absent.
+    pub fn verify<V: fmt::Display>(
+        &self,
+        root: &str,
+        key: &K,
+        value: Option<&V>,
+        hash_fn: &dyn Fn(&str) -> String,
+    ) -> bool {
+        let mut expected = root;
+        let mut valid = true;
+        let mut found = false;
+
+        // Every node of the path is hashed and compared, even after a mismatch, so the time
+        // taken doesn't tell at which depth a forged path diverges.
+        for node in &self.path {
+            let hash = node_hash(
+                hash_fn,
+                &node.key.to_string(),
+                &node.value_hash,
+                &node.left,
+                &node.right,
+            );
+            // Nothing comes after the node of the key
+            valid &= !found
+                & !expected.is_empty()
+                & constant_time_eq(hash.as_bytes(), expected.as_bytes());
+            match key.cmp(&node.key) {
+                Ordering::Less => expected = &node.left,
+                Ordering::Greater => expected = &node.right,
+                Ordering::Equal => {
+                    let value_hash = value.map(|v| hash_fn(&v.to_string()));
+                    valid &= value_hash.is_some_and(|h| {
+                        constant_time_eq(h.as_bytes(), node.value_hash.as_bytes())
+                    });
+                    found = true;
+                }
+            }
+        }
+        // Otherwise the search ended on an empty subtree
+        valid & (found | (value.is_none() & expected.is_empty()))
+    }
+}
+
+impl<K: Ord + fmt::Display, V> BinarySearchTree<K, V> {
     fn print_node(
         f: &mut fmt::Formatter<'_>,
-        node: &Node,
+        node: &Node<K, V>,
         level: usize,
         left_right: Dir,
     ) -> fmt::Result {
@@ -117,28 +608,28 @@ impl Tree {
             f,
             "{}{}{}-{}",
             indent,
-            Tree::get_arrow(level),
+            BinarySearchTree::<K, V>::get_arrow(level),
             dir,
-            node.item
+            node.key
         )?;
         if let Some(left_node) = &node.left {
-            Tree::print_node(f, left_node.as_ref(), level + 1, Dir::Left)?;
+            BinarySearchTree::print_node(f, left_node.as_ref(), level + 1, Dir::Left)?;
         } else {
             writeln!(
                 f,
                 "{}{}left-None",
                 "    ".repeat(level + 1),
-                Tree::get_arrow(level),
+                BinarySearchTree::<K, V>::get_arrow(level),
             )?;
         }
         if let Some(right_node) = &node.right {
-            Tree::print_node(f, right_node.as_ref(), level + 1, Dir::Right)?;
+            BinarySearchTree::print_node(f, right_node.as_ref(), level + 1, Dir::Right)?;
         } else {
             writeln!(
                 f,
                 "{}{}right-None",
                 "    ".repeat(level + 1),
-                Tree::get_arrow(level),
+                BinarySearchTree::<K, V>::get_arrow(level),
             )?;
         }
         Ok(())
@@ -152,17 +643,370 @@ impl Tree {
     }
 }
 
+pub type Iter<'a, K, V> = Range<'a, K, V, RangeFull>;
+
+pub struct Range<'a, K, V, R> {
+    // Nodes whose left subtree was already pushed, the next one on top
+    stack: Vec<&'a Node<K, V>>,
+    range: R,
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> Range<'a, K, V, R> {
+    fn push_left(&mut self, mut link: &'a Leaf<K, V>) {
+        while let Some(node) = link {
+            let above_start = match self.range.start_bound() {
+                Bound::Included(start) => node.key >= *start,
+                Bound::Excluded(start) => node.key > *start,
+                Bound::Unbounded => true,
+            };
+            if above_start {
+                self.stack.push(node);
+                link = &node.left;
+            } else {
+                // The whole left subtree is before the start too
+                link = &node.right;
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> Iterator for Range<'a, K, V, R> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let below_end = match self.range.end_bound() {
+            Bound::Included(end) => node.key <= *end,
+            Bound::Excluded(end) => node.key < *end,
+            Bound::Unbounded => true,
+        };
+        if !below_end {
+            self.stack.clear();
+            return None;
+        }
+        self.push_left(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a BinarySearchTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// Node, then left subtree, then right subtree
+pub struct PreOrder<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for PreOrder<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        // Right first, so the left subtree comes out first
+        if let Some(right) = &node.right {
+            self.stack.push(right);
+        }
+        if let Some(left) = &node.left {
+            self.stack.push(left);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
+// Left subtree, then right subtree, then node
+pub struct PostOrder<'a, K, V> {
+    // The flag tells if the children of the node were already pushed
+    stack: Vec<(&'a Node<K, V>, bool)>,
+}
+
+impl<'a, K, V> Iterator for PostOrder<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, expanded)) = self.stack.pop() {
+            if expanded {
+                return Some((&node.key, &node.value));
+            }
+            self.stack.push((node, true));
+            if let Some(right) = &node.right {
+                self.stack.push((right, false));
+            }
+            if let Some(left) = &node.left {
+                self.stack.push((left, false));
+            }
+        }
+        None
+    }
+}
+
+// Breadth-first, level by level from the root
+pub struct LevelOrder<'a, K, V> {
+    queue: VecDeque<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for LevelOrder<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.queue.pop_front()?;
+        if let Some(left) = &node.left {
+            self.queue.push_back(left);
+        }
+        if let Some(right) = &node.right {
+            self.queue.push_back(right);
+        }
+        Some((&node.key, &node.value))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merkle_tree_vec::keccak256;
+
+    fn tree() -> BinarySearchTree<i32, String> {
+        let mut tree = BinarySearchTree::new();
+        for key in [8, 10, 3, 7, 2, 14, 9] {
+            tree.insert(key, key.to_string());
+        }
+        tree
+    }
+
+    fn keys<'a>(iter: impl Iterator<Item = (&'a i32, &'a String)>) -> Vec<i32> {
+        iter.map(|(k, _)| *k).collect()
+    }
+
     #[test]
     fn test_binary_tree() {
-        let mut tree = Tree::new();
-        tree.insert(8);
-        tree.insert(10);
-        tree.insert(3);
-        tree.insert(7);
-        tree.insert(2);
+        let mut tree = BinarySearchTree::new();
+        tree.insert(8, ());
+        tree.insert(10, ());
+        tree.insert(3, ());
+        tree.insert(7, ());
+        tree.insert(2, ());
         println!("{tree}");
+
+        let expected = "\
+Tree:
+root-8
+    └──>left-3
+        └──>left-2
+            └──>left-None
+            └──>right-None
+        └──>right-7
+            └──>left-None
+            └──>right-None
+    └──>right-10
+        └──>left-None
+        └──>right-None
+";
+        assert_eq!(format!("{tree}"), expected);
+        assert_eq!(
+            format!("{}", BinarySearchTree::<i32, ()>::new()),
+            "Empty Tree"
+        );
+    }
+    #[test]
+    fn test_insert_get_remove() {
+        let mut tree = tree();
+        assert_eq!(tree.len(), 7);
+        assert_eq!(tree.get(&7), Some(&"7".to_string()));
+        assert!(tree.contains(&14));
+        assert!(!tree.contains(&5));
+
+        assert_eq!(tree.insert(7, "seven".to_string()), Some("7".to_string()));
+        tree.get_mut(&9).unwrap().push('!');
+        assert_eq!(tree.get(&9), Some(&"9!".to_string()));
+        assert!(tree.get_mut(&5).is_err());
+        assert_eq!(tree.len(), 7);
+
+        // Leaf, node with one child, node with two children, root
+        assert_eq!(tree.remove(&2), Some("2".to_string()));
+        assert_eq!(tree.remove(&14), Some("14".to_string()));
+        assert_eq!(tree.remove(&3), Some("3".to_string()));
+        assert_eq!(tree.remove(&8), Some("8".to_string()));
+        assert_eq!(tree.remove(&8), None);
+        assert_eq!(keys(tree.iter()), vec![7, 9, 10]);
+        assert_eq!(tree.len(), 3);
+    }
+    #[test]
+    fn test_min_max() {
+        let tree = tree();
+        assert_eq!(tree.min(), Some((&2, &"2".to_string())));
+        assert_eq!(tree.max(), Some((&14, &"14".to_string())));
+        assert!(BinarySearchTree::<i32, ()>::new().min().is_none());
+    }
+    #[test]
+    fn test_traversals() {
+        let tree = tree();
+        assert_eq!(keys(tree.iter()), vec![2, 3, 7, 8, 9, 10, 14]);
+        assert_eq!(keys(tree.pre_order()), vec![8, 3, 2, 7, 10, 9, 14]);
+        assert_eq!(keys(tree.post_order()), vec![2, 7, 3, 9, 14, 10, 8]);
+        assert_eq!(keys(tree.level_order()), vec![8, 3, 10, 2, 7, 9, 14]);
+        assert_eq!(keys((&tree).into_iter()), keys(tree.iter()));
+    }
+    #[test]
+    fn test_range() {
+        let tree = tree();
+        assert_eq!(keys(tree.range(3..9)), vec![3, 7, 8]);
+        assert_eq!(keys(tree.range(4..=10)), vec![7, 8, 9, 10]);
+        assert_eq!(keys(tree.range(..3)), vec![2]);
+        assert_eq!(keys(tree.range(11..)), vec![14]);
+        assert_eq!(
+            keys(tree.range((Bound::Excluded(2), Bound::Excluded(7)))),
+            vec![3]
+        );
+        assert!(keys(tree.range(15..)).is_empty());
+    }
+    // Checks the AVL invariant and the stored heights, returns the height
+    fn check_avl<K, V>(link: &Leaf<K, V>) -> usize {
+        match link {
+            None => 0,
+            Some(node) => {
+                let (left, right) = (check_avl(&node.left), check_avl(&node.right));
+                assert!(left.abs_diff(right) <= 1);
+                assert_eq!(node.height, 1 + left.max(right));
+                node.height
+            }
+        }
+    }
+
+    #[test]
+    fn test_avl_sorted_input() {
+        let mut plain = BinarySearchTree::new();
+        let mut avl = BinarySearchTree::with_balancing(Balancing::Avl);
+        for key in 0..1000 {
+            plain.insert(key, ());
+            avl.insert(key, ());
+        }
+        assert_eq!(plain.height(), 1000);
+        // An AVL tree is at most ~1.44 log2(n) high
+        assert!(avl.height() <= 14);
+        assert_eq!(check_avl(&avl.root), avl.height());
+        assert_eq!(avl.len(), 1000);
+        assert!(avl.iter().map(|(k, _)| *k).eq(0..1000));
+    }
+    #[test]
+    fn test_avl_rotations() {
+        let mut avl = BinarySearchTree::with_balancing(Balancing::Avl);
+        avl.insert(1, ());
+        avl.insert(3, ());
+        // Right-left case: 2 ends up at the root
+        avl.insert(2, ());
+
+        let expected = "\
+Tree:
+root-2
+    └──>left-1
+        └──>left-None
+        └──>right-None
+    └──>right-3
+        └──>left-None
+        └──>right-None
+";
+        assert_eq!(format!("{avl}"), expected);
+    }
+    #[test]
+    fn test_avl_remove() {
+        let mut avl = BinarySearchTree::with_balancing(Balancing::Avl);
+        for key in 0..200 {
+            assert_eq!(avl.insert(key, key * 10), None);
+        }
+        assert_eq!(avl.insert(7, 0), Some(70));
+
+        for key in (0..200).step_by(3) {
+            assert!(avl.remove(&key).is_some());
+            check_avl(&avl.root);
+        }
+        assert_eq!(avl.remove(&0), None);
+        assert_eq!(avl.len(), 200 - 67);
+        assert!(avl
+            .iter()
+            .map(|(k, _)| *k)
+            .eq((0..200).filter(|k| k % 3 != 0)));
+        assert_eq!(avl.get(&8), Some(&80));
+    }
+    #[test]
+    fn test_authenticated_root() {
+        let mut tree = BinarySearchTree::authenticated(keccak256);
+        assert_eq!(tree.root_hash(), Some(String::new()));
+        assert_eq!(BinarySearchTree::<i32, ()>::new().root_hash(), None);
+
+        tree.insert(2, "two");
+        tree.insert(1, "one");
+        // root-2 with 1 on its left
+        let one = node_hash(&keccak256, "1", &keccak256("one"), "", "");
+        let root = node_hash(&keccak256, "2", &keccak256("two"), &one, "");
+        assert_eq!(tree.root_hash(), Some(root.clone()));
+
+        // Same content, inserted in another order: same shape after the rotations
+        let mut other = BinarySearchTree::authenticated(keccak256);
+        for key in [3, 1, 2] {
+            other.insert(key, "x");
+        }
+        let mut rotated = BinarySearchTree::authenticated(keccak256);
+        for key in [1, 2, 3] {
+            rotated.insert(key, "x");
+        }
+        assert_eq!(other.root_hash(), rotated.root_hash());
+
+        tree.insert(1, "changed");
+        assert_ne!(tree.root_hash(), Some(root));
+        assert_eq!(
+            tree.get_mut(&1),
+            Err("Read-only authenticated tree: use insert".to_string())
+        );
+    }
+    #[test]
+    fn test_authenticated_proofs() {
+        let mut tree = BinarySearchTree::authenticated(keccak256);
+        for key in (0..100).step_by(2) {
+            tree.insert(key, key * 10);
+        }
+        tree.remove(&40);
+        let root = tree.root_hash().unwrap();
+
+        for key in 0..100 {
+            let proof = tree.get_proof(&key).unwrap();
+            match tree.get(&key) {
+                Some(value) => {
+                    assert!(proof.verify(&root, &key, Some(value), &keccak256));
+                    assert!(!proof.verify(&root, &key, Some(&(value + 1)), &keccak256));
+                    assert!(!proof.verify::<i32>(&root, &key, None, &keccak256));
+                }
+                None => {
+                    assert!(proof.verify::<i32>(&root, &key, None, &keccak256));
+                    assert!(!proof.verify(&root, &key, Some(&0), &keccak256));
+                }
+            }
+        }
+
+        // An absence proof of 40 doesn't hold for a present neighbour
+        let proof = tree.get_proof(&40).unwrap();
+        assert!(!proof.verify::<i32>(&root, &42, None, &keccak256));
+
+        // Tampered path
+        let mut proof = tree.get_proof(&42).unwrap();
+        proof.path[0].value_hash = keccak256("X");
+        assert!(!proof.verify(&root, &42, Some(&420), &keccak256));
+        let mut proof = tree.get_proof(&42).unwrap();
+        let last = proof.path.len() - 1;
+        proof.path[last].left = keccak256("X");
+        assert!(!proof.verify(&root, &42, Some(&420), &keccak256));
+        // Nothing may follow the node of the key
+        let mut proof = tree.get_proof(&42).unwrap();
+        proof.path.push(proof.path[last].clone());
+        assert!(!proof.verify(&root, &42, Some(&420), &keccak256));
+
+        let empty = BinarySearchTree::<i32, i32>::authenticated(keccak256);
+        let proof = empty.get_proof(&1).unwrap();
+        assert!(proof.verify::<i32>("", &1, None, &keccak256));
+        assert!(BinarySearchTree::<i32, i32>::new().get_proof(&1).is_err());
     }
 }
@@ -0,0 +1,565 @@
+// Bitcoin transaction Merkle trees.
+// MerkleTreeVec already duplicates the last node of odd levels like Bitcoin does, but Bitcoin hashes
+// the raw 32 byte txids (in internal byte order) with double SHA-256, and a block with a single
+// transaction has that txid as its merkle root.
+// Txids and roots are usually displayed (block explorers, RPC) with their bytes reversed,
+// use hash_from_hex and hash_to_hex to convert from/to that representation.
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use crate::hashers::constant_time_eq;
+
+pub type Hash256 = [u8; 32];
+
+pub fn double_sha256(data: &[u8]) -> Hash256 {
+    let mut first = [0u8; 32];
+    let mut sha256 = Sha256::new();
+    sha256.input(data);
+    sha256.result(&mut first);
+
+    let mut second = [0u8; 32];
+    let mut sha256 = Sha256::new();
+    sha256.input(&first);
+    sha256.result(&mut second);
+    second
+}
+
+fn hash_pair(left: &Hash256, right: &Hash256) -> Hash256 {
+    let mut concat = [0u8; 64];
+    concat[..32].copy_from_slice(left);
+    concat[32..].copy_from_slice(right);
+    double_sha256(&concat)
+}
+
+// Parses a txid or block hash as displayed, returning it in internal byte order.
+pub fn hash_from_hex(s: &str) -> Result<Hash256, String> {
+    if s.len() != 64 || !s.is_ascii() {
+        return Err("Invalid hash length".to_string());
+    }
+    let mut hash = [0u8; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|e| e.to_string())?;
+    }
+    hash.reverse();
+    Ok(hash)
+}
+
+pub fn hash_to_hex(hash: &Hash256) -> String {
+    hash.iter().rev().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn merkle_root(txids: &[Hash256]) -> Result<Hash256, String> {
+    if txids.is_empty() {
+        return Err("Empty tree".to_string());
+    }
+    let mut upper_children = txids.to_vec();
+
+    while upper_children.len() > 1 {
+        upper_children = upper_children
+            .chunks(2)
+            .map(|chunk| {
+                // "Balance" the tree -> Duplicate if there is no right leaf
+                hash_pair(&chunk[0], chunk.last().unwrap())
+            })
+            .collect();
+    }
+    Ok(upper_children[0])
+}
+
+// Simplified Payment Verification proof: the siblings of a txid up to the merkle root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpvProof {
+    pub txid: Hash256,
+    pub index: usize,
+    pub siblings: Vec<Hash256>,
+}
+
+pub fn get_spv_proof(txids: &[Hash256], index: usize) -> Result<SpvProof, String> {
+    if index >= txids.len() {
+        return Err("Index out of bounds".to_string());
+    }
+    let mut upper_children = txids.to_vec();
+    let mut siblings = Vec::new();
+    let mut index_item = index;
+
+    while upper_children.len() > 1 {
+        // "Balance" the tree -> The last node of an odd level is its own sibling
+        let sibling = upper_children
+            .get(index_item ^ 1)
+            .unwrap_or(&upper_children[index_item]);
+        siblings.push(*sibling);
+
+        upper_children = upper_children
+            .chunks(2)
+            .map(|chunk| hash_pair(&chunk[0], chunk.last().unwrap()))
+            .collect();
+        index_item /= 2;
+    }
+
+    Ok(SpvProof {
+        txid: txids[index],
+        index,
+        siblings,
+    })
+}
+
+// Height of the tree of a block with tx_count transactions, 0 for a single one
+fn tree_height(tx_count: usize) -> usize {
+    tx_count.next_power_of_two().trailing_zeros() as usize
+}
+
+// tx_count is the number of transactions of the block (e.g. total_transactions of a merkleblock).
+// The proof has to reach the leaves of that tree: a 64 byte transaction also hashes like an inner
+// node, and a shorter proof would make its two halves pass for a "txid".
+pub fn verify_spv_proof(root: &Hash256, proof: &SpvProof, tx_count: usize) -> bool {
+    if proof.index >= tx_count || proof.siblings.len() != tree_height(tx_count) {
+        return false;
+    }
+    let mut check_root = proof.txid;
+    let mut index = proof.index;
+    for sibling in &proof.siblings {
+        check_root = if index.is_multiple_of(2) {
+            hash_pair(&check_root, sibling)
+        } else {
+            hash_pair(sibling, &check_root)
+        };
+        index /= 2;
+    }
+    // Any leftover bit means the index is not in a tree of that height
+    (index == 0) & constant_time_eq(&check_root, root)
+}
+
+// Partial Merkle tree of BIP37 merkleblock messages: the hashes and flag bits from a depth-first
+// traversal that only descends into subtrees containing a matched transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialMerkleTree {
+    pub total_transactions: u32,
+    pub hashes: Vec<Hash256>,
+    pub flags: Vec<bool>,
+}
+
+impl PartialMerkleTree {
+    pub fn new(txids: &[Hash256], matches: &[bool]) -> Result<PartialMerkleTree, String> {
+        if txids.is_empty() || txids.len() != matches.len() {
+            return Err("Invalid transactions".to_string());
+        }
+        let mut pmt = PartialMerkleTree {
+            total_transactions: txids.len() as u32,
+            hashes: Vec::new(),
+            flags: Vec::new(),
+        };
+        let height = pmt.height();
+        pmt.traverse_and_build(height, 0, txids, matches);
+        Ok(pmt)
+    }
+
+    // Returns the merkle root and the matched (index, txid) pairs.
+    pub fn extract_matches(&self) -> Result<(Hash256, Vec<(usize, Hash256)>), String> {
+        if self.total_transactions == 0 {
+            return Err("No transactions".to_string());
+        }
+        if self.hashes.len() > self.total_transactions as usize {
+            return Err("More hashes than transactions".to_string());
+        }
+        if self.flags.len() < self.hashes.len() {
+            return Err("Fewer flag bits than hashes".to_string());
+        }
+        let mut bits_used = 0;
+        let mut hashes_used = 0;
+        let mut matches = Vec::new();
+
+        let root = self.traverse_and_extract(
+            self.height(),
+            0,
+            &mut bits_used,
+            &mut hashes_used,
+            &mut matches,
+        )?;
+
+        // Only the padding of the last flag byte can be left unused
+        if bits_used.div_ceil(8) != self.flags.len().div_ceil(8) {
+            return Err("Unused flag bits".to_string());
+        }
+        if hashes_used != self.hashes.len() {
+            return Err("Unused hashes".to_string());
+        }
+        Ok((root, matches))
+    }
+
+    // u32 (little endian) transaction count, then the hashes and the flag bytes,
+    // each prefixed by its CompactSize length. Flag bits are packed least significant bit first.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.total_transactions.to_le_bytes().to_vec();
+
+        write_compact_size(&mut bytes, self.hashes.len() as u64);
+        for hash in &self.hashes {
+            bytes.extend_from_slice(hash);
+        }
+
+        let mut flag_bytes = vec![0u8; self.flags.len().div_ceil(8)];
+        for (i, flag) in self.flags.iter().enumerate() {
+            flag_bytes[i / 8] |= (*flag as u8) << (i % 8);
+        }
+        write_compact_size(&mut bytes, flag_bytes.len() as u64);
+        bytes.extend_from_slice(&flag_bytes);
+
+        bytes
+    }
+
+    // Returns the tree and the number of bytes read.
+    pub fn deserialize(bytes: &[u8]) -> Result<(PartialMerkleTree, usize), String> {
+        let mut reader = ByteReader { bytes, pos: 0 };
+
+        let total_transactions = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+
+        let hashes_count = reader.compact_size()?;
+        let mut hashes = Vec::new();
+        for _ in 0..hashes_count {
+            hashes.push(reader.take(32)?.try_into().unwrap());
+        }
+
+        let flags_count = reader.compact_size()?;
+        let flags = reader
+            .take(flags_count)?
+            .iter()
+            .flat_map(|byte| (0..8).map(move |bit| (byte >> bit) & 1 == 1))
+            .collect();
+
+        Ok((
+            PartialMerkleTree {
+                total_transactions,
+                hashes,
+                flags,
+            },
+            reader.pos,
+        ))
+    }
+
+    fn tree_width(&self, height: usize) -> usize {
+        (self.total_transactions as usize + (1 << height) - 1) >> height
+    }
+
+    fn height(&self) -> usize {
+        tree_height(self.total_transactions as usize)
+    }
+
+    fn calc_hash(&self, height: usize, pos: usize, txids: &[Hash256]) -> Hash256 {
+        if height == 0 {
+            return txids[pos];
+        }
+        let left = self.calc_hash(height - 1, pos * 2, txids);
+        let right = if pos * 2 + 1 < self.tree_width(height - 1) {
+            self.calc_hash(height - 1, pos * 2 + 1, txids)
+        } else {
+            left
+        };
+        hash_pair(&left, &right)
+    }
+
+    fn traverse_and_build(
+        &mut self,
+        height: usize,
+        pos: usize,
+        txids: &[Hash256],
+        matches: &[bool],
+    ) {
+        let start = pos << height;
+        let end = ((pos + 1) << height).min(txids.len());
+        let parent_of_match = matches[start..end].iter().any(|m| *m);
+
+        self.flags.push(parent_of_match);
+        if height == 0 || !parent_of_match {
+            let hash = self.calc_hash(height, pos, txids);
+            self.hashes.push(hash);
+        } else {
+            self.traverse_and_build(height - 1, pos * 2, txids, matches);
+            if pos * 2 + 1 < self.tree_width(height - 1) {
+                self.traverse_and_build(height - 1, pos * 2 + 1, txids, matches);
+            }
+        }
+    }
+
+    fn traverse_and_extract(
+        &self,
+        height: usize,
+        pos: usize,
+        bits_used: &mut usize,
+        hashes_used: &mut usize,
+        matches: &mut Vec<(usize, Hash256)>,
+    ) -> Result<Hash256, String> {
+        let parent_of_match = *self
+            .flags
+            .get(*bits_used)
+            .ok_or("Not enough flag bits".to_string())?;
+        *bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self
+                .hashes
+                .get(*hashes_used)
+                .ok_or("Not enough hashes".to_string())?;
+            *hashes_used += 1;
+            if height == 0 && parent_of_match {
+                matches.push((pos, hash));
+            }
+            return Ok(hash);
+        }
+
+        let left =
+            self.traverse_and_extract(height - 1, pos * 2, bits_used, hashes_used, matches)?;
+        let right = if pos * 2 + 1 < self.tree_width(height - 1) {
+            let right = self.traverse_and_extract(
+                height - 1,
+                pos * 2 + 1,
+                bits_used,
+                hashes_used,
+                matches,
+            )?;
+            // Identical siblings would allow two trees with the same root (CVE-2012-2459)
+            if right == left {
+                return Err("Duplicated hashes".to_string());
+            }
+            right
+        } else {
+            left
+        };
+        Ok(hash_pair(&left, &right))
+    }
+}
+
+// A merkleblock message: the 80 byte block header followed by the partial Merkle tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleBlock {
+    pub header: [u8; 80],
+    pub tree: PartialMerkleTree,
+}
+
+impl MerkleBlock {
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = self.header.to_vec();
+        bytes.extend(self.tree.serialize());
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<MerkleBlock, String> {
+        if bytes.len() < 80 {
+            return Err("Truncated header".to_string());
+        }
+        let (tree, len) = PartialMerkleTree::deserialize(&bytes[80..])?;
+        if 80 + len != bytes.len() {
+            return Err("Trailing bytes".to_string());
+        }
+        Ok(MerkleBlock {
+            header: bytes[..80].try_into().unwrap(),
+            tree,
+        })
+    }
+
+    // The merkle root field of the header: after the version and the previous block hash.
+    pub fn merkle_root(&self) -> Hash256 {
+        self.header[36..68].try_into().unwrap()
+    }
+
+    // The matched (index, txid) pairs, if the partial tree commits to the header's merkle root.
+    pub fn extract_matches(&self) -> Result<Vec<(usize, Hash256)>, String> {
+        let (root, matches) = self.tree.extract_matches()?;
+        if root != self.merkle_root() {
+            return Err("Merkle root mismatch".to_string());
+        }
+        Ok(matches)
+    }
+}
+
+fn write_compact_size(bytes: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => bytes.push(n as u8),
+        0xfd..=0xffff => {
+            bytes.push(0xfd);
+            bytes.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            bytes.push(0xfe);
+            bytes.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            bytes.push(0xff);
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err("Unexpected end of data".to_string());
+        }
+        let taken = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(taken)
+    }
+
+    fn compact_size(&mut self) -> Result<usize, String> {
+        let n = match self.take(1)?[0] {
+            0xfd => u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as u64,
+            0xfe => u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as u64,
+            0xff => u64::from_le_bytes(self.take(8)?.try_into().unwrap()),
+            n => n as u64,
+        };
+        usize::try_from(n).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Block 100000
+    const BLOCK_100000_ROOT: &str =
+        "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+    const BLOCK_100000_TXIDS: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+
+    fn block_100000_txids() -> Vec<Hash256> {
+        BLOCK_100000_TXIDS
+            .iter()
+            .map(|s| hash_from_hex(s).unwrap())
+            .collect()
+    }
+
+    fn test_txids(n: usize) -> Vec<Hash256> {
+        (0..n).map(|i| double_sha256(&i.to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn test_merkle_root_block_100000() {
+        let root = merkle_root(&block_100000_txids()).unwrap();
+        assert_eq!(hash_to_hex(&root), BLOCK_100000_ROOT);
+    }
+    #[test]
+    fn test_merkle_root_single_transaction() {
+        // Block 1, the coinbase txid is the merkle root
+        let txid =
+            hash_from_hex("0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098")
+                .unwrap();
+        assert_eq!(merkle_root(&[txid]).unwrap(), txid);
+    }
+    #[test]
+    fn test_spv_proof() {
+        let txids = block_100000_txids();
+        let root = hash_from_hex(BLOCK_100000_ROOT).unwrap();
+
+        for index in 0..txids.len() {
+            let proof = get_spv_proof(&txids, index).unwrap();
+            assert!(verify_spv_proof(&root, &proof, txids.len()));
+        }
+
+        let mut proof = get_spv_proof(&txids, 2).unwrap();
+        proof.index = 3;
+        assert!(!verify_spv_proof(&root, &proof, txids.len()));
+
+        // Odd number of transactions
+        let txids = test_txids(5);
+        let root = merkle_root(&txids).unwrap();
+        let proof = get_spv_proof(&txids, 4).unwrap();
+        assert!(verify_spv_proof(&root, &proof, 5));
+        // The duplicated last node is not a sixth transaction
+        let mut duplicate = proof.clone();
+        duplicate.index = 5;
+        assert!(!verify_spv_proof(&root, &duplicate, 5));
+        assert!(verify_spv_proof(
+            &merkle_root(&txids[..1]).unwrap(),
+            &get_spv_proof(&txids[..1], 0).unwrap(),
+            1
+        ));
+    }
+    #[test]
+    fn test_spv_proof_of_inner_node() {
+        let txids = test_txids(4);
+        let root = merkle_root(&txids).unwrap();
+
+        // The 64 bytes of txids 0 and 1 hash to their parent, which is proven like a txid
+        let forged = SpvProof {
+            txid: hash_pair(&txids[0], &txids[1]),
+            index: 0,
+            siblings: vec![hash_pair(&txids[2], &txids[3])],
+        };
+        assert!(!verify_spv_proof(&root, &forged, 4));
+        // Without the number of transactions, the height of the proof could not be checked
+        assert_eq!(hash_pair(&forged.txid, &forged.siblings[0]), root);
+    }
+    #[test]
+    fn test_partial_merkle_tree() {
+        for n in 1..=13 {
+            let txids = test_txids(n);
+            let root = merkle_root(&txids).unwrap();
+            let matches = (0..n).map(|i| i % 3 == 1).collect::<Vec<bool>>();
+
+            let pmt = PartialMerkleTree::new(&txids, &matches).unwrap();
+            let (pmt_root, matched) = pmt.extract_matches().unwrap();
+
+            assert_eq!(pmt_root, root);
+            let expected = (0..n)
+                .filter(|i| matches[*i])
+                .map(|i| (i, txids[i]))
+                .collect::<Vec<(usize, Hash256)>>();
+            assert_eq!(matched, expected);
+        }
+    }
+    #[test]
+    fn test_partial_merkle_tree_serialization() {
+        let txids = test_txids(7);
+        let matches = [false, false, true, false, false, false, true];
+        let pmt = PartialMerkleTree::new(&txids, &matches).unwrap();
+
+        let bytes = pmt.serialize();
+        assert_eq!(&bytes[..4], &7u32.to_le_bytes());
+
+        let (decoded, len) = PartialMerkleTree::deserialize(&bytes).unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(decoded.hashes, pmt.hashes);
+        // The flags are padded to whole bytes
+        assert_eq!(&decoded.flags[..pmt.flags.len()], &pmt.flags[..]);
+        assert_eq!(decoded.extract_matches(), pmt.extract_matches());
+    }
+    #[test]
+    fn test_partial_merkle_tree_rejects_duplicates() {
+        let txids = test_txids(3);
+        let mut pmt = PartialMerkleTree::new(&txids, &[true, true, true]).unwrap();
+        assert!(pmt.extract_matches().is_ok());
+
+        // Txids: [a, b, c] and [a, b, c, c] have the same root
+        pmt.total_transactions = 4;
+        pmt.flags = vec![true, true, true, true, true, true, true];
+        pmt.hashes.push(txids[2]);
+        assert!(pmt.extract_matches().is_err());
+    }
+    #[test]
+    fn test_merkle_block() {
+        let txids = block_100000_txids();
+        let mut header = [0u8; 80];
+        header[36..68].copy_from_slice(&hash_from_hex(BLOCK_100000_ROOT).unwrap());
+
+        let merkle_block = MerkleBlock {
+            header,
+            tree: PartialMerkleTree::new(&txids, &[false, false, true, false]).unwrap(),
+        };
+        let decoded = MerkleBlock::deserialize(&merkle_block.serialize()).unwrap();
+        assert_eq!(decoded.extract_matches().unwrap(), vec![(2, txids[2])]);
+
+        let mut wrong = decoded.clone();
+        wrong.header[36] ^= 1;
+        assert!(wrong.extract_matches().is_err());
+        assert!(MerkleBlock::deserialize(&merkle_block.serialize()[..100]).is_err());
+    }
+}
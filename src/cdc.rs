@@ -0,0 +1,349 @@
+// Content-defined chunking (FastCDC): chunk boundaries are chosen where a rolling "gear" hash of
+// the content matches a mask, so inserting or removing bytes only moves the boundaries around the
+// edit and every other chunk, and so every other leaf of the tree, stays the same.
+// A stricter mask is used before the average size and a looser one after it (normalized chunking),
+// which keeps most chunks close to the average.
+use std::collections::HashSet;
+use std::io::Read;
+
+use crate::merkle_tree_vec::{diff_levels, keccak256, MerkleTreeVec};
+use crate::piece_tree::hash_piece;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkerConfig {
+    pub min_size: usize,
+    // Rounded down to a power of two
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        ChunkerConfig {
+            min_size: 2 * 1024,
+            avg_size: 8 * 1024,
+            max_size: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub offset: u64,
+    pub len: usize,
+    pub hash: String,
+}
+
+pub struct ChunkTree {
+    pub chunks: Vec<Chunk>,
+    pub tree: MerkleTreeVec<'static>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkStats {
+    pub count: usize,
+    pub total_bytes: u64,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub mean_size: usize,
+}
+
+// Chunks of a new version compared to the previous one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReuseStats {
+    pub reused_chunks: usize,
+    pub reused_bytes: u64,
+    pub new_chunks: usize,
+    pub new_bytes: u64,
+}
+
+// Random values for every byte, generated with SplitMix64 so the table is fixed
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+impl ChunkerConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.min_size == 0 || self.min_size > self.avg_size || self.avg_size > self.max_size {
+            return Err("Chunk sizes must satisfy 0 < min <= avg <= max".to_string());
+        }
+        Ok(())
+    }
+
+    // The gear hash shifts left, so its high bits depend on the most bytes: the masks use them
+    fn masks(&self) -> (u64, u64) {
+        let bits = self.avg_size.ilog2();
+        let mask = |bits: u32| match bits {
+            0 => 0,
+            _ => (u64::MAX >> (64 - bits)) << (64 - bits),
+        };
+        (mask((bits + 1).min(64)), mask(bits.saturating_sub(1)))
+    }
+
+    // Length of the first chunk of data, which has to hold max_size bytes unless it is the end
+    pub fn cut(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+        let end = data.len().min(self.max_size);
+        let normal = end.min(self.avg_size);
+        let (mask_small, mask_large) = self.masks();
+        let mut hash = 0u64;
+
+        for (i, byte) in data.iter().enumerate().take(end).skip(self.min_size) {
+            hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+            let mask = if i < normal { mask_small } else { mask_large };
+            if hash & mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+
+    pub fn chunks(&self, mut reader: impl Read) -> Result<Vec<Chunk>, String> {
+        self.validate()?;
+        let mut buf = Vec::with_capacity(self.max_size);
+        let mut chunks = Vec::new();
+        let mut offset = 0u64;
+        let mut eof = false;
+
+        loop {
+            // A boundary is only searched with max_size bytes ahead, or at the end of the reader
+            while !eof && buf.len() < self.max_size {
+                let missing = (self.max_size - buf.len()) as u64;
+                let n = (&mut reader)
+                    .take(missing)
+                    .read_to_end(&mut buf)
+                    .map_err(|e| e.to_string())?;
+                eof = n == 0;
+            }
+            if buf.is_empty() {
+                break;
+            }
+
+            let len = self.cut(&buf);
+            chunks.push(Chunk {
+                offset,
+                len,
+                hash: hash_piece(&buf[..len]),
+            });
+            offset += len as u64;
+            buf.drain(..len);
+        }
+        Ok(chunks)
+    }
+}
+
+impl ChunkTree {
+    pub fn new(reader: impl Read, config: ChunkerConfig) -> Result<ChunkTree, String> {
+        let chunks = config.chunks(reader)?;
+        if chunks.is_empty() {
+            return Err("Empty file".to_string());
+        }
+        let leaves = chunks
+            .iter()
+            .map(|c| c.hash.clone())
+            .collect::<Vec<String>>();
+
+        Ok(ChunkTree {
+            chunks,
+            tree: MerkleTreeVec::new(&leaves, &keccak256),
+        })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.tree.root
+    }
+
+    pub fn stats(&self) -> ChunkStats {
+        let total_bytes = self.chunks.iter().map(|c| c.len as u64).sum();
+        ChunkStats {
+            count: self.chunks.len(),
+            total_bytes,
+            min_size: self.chunks.iter().map(|c| c.len).min().unwrap_or(0),
+            max_size: self.chunks.iter().map(|c| c.len).max().unwrap_or(0),
+            mean_size: (total_bytes / self.chunks.len() as u64) as usize,
+        }
+    }
+
+    // How many chunks of self already were in `previous`, so don't have to be stored or sent again.
+    pub fn reuse(&self, previous: &ChunkTree) -> ReuseStats {
+        let known = previous
+            .chunks
+            .iter()
+            .map(|c| c.hash.as_str())
+            .collect::<HashSet<&str>>();
+
+        let mut stats = ReuseStats::default();
+        for chunk in &self.chunks {
+            if known.contains(chunk.hash.as_str()) {
+                stats.reused_chunks += 1;
+                stats.reused_bytes += chunk.len as u64;
+            } else {
+                stats.new_chunks += 1;
+                stats.new_bytes += chunk.len as u64;
+            }
+        }
+        stats
+    }
+
+    // Indices of the chunks of `other` that are not at the same place in self. With as many chunks
+    // on both sides, the trees are compared top-down and only the subtrees above edited chunks are
+    // visited (see diff_levels). Otherwise an edit added or removed chunks and shifted the ones
+    // after it: the chunks are matched from both ends, and the ones in between are changed.
+    pub fn diff(&self, other: &ChunkTree) -> Vec<usize> {
+        if let Ok(indices) = diff_levels(&self.tree.levels(), &other.tree.levels()) {
+            return indices;
+        }
+        let same = |a: &Chunk, b: &Chunk| a.hash == b.hash;
+        let prefix = self
+            .chunks
+            .iter()
+            .zip(&other.chunks)
+            .take_while(|(a, b)| same(a, b))
+            .count();
+        let max_suffix = self.chunks.len().min(other.chunks.len()) - prefix;
+        let suffix = self
+            .chunks
+            .iter()
+            .rev()
+            .zip(other.chunks.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| same(a, b))
+            .count();
+        (prefix..other.chunks.len() - suffix).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_cover_the_input() {
+        let data = random_bytes(300 * 1024, 1);
+        let config = ChunkerConfig::default();
+        let chunks = config.chunks(Cursor::new(&data)).unwrap();
+
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.offset, offset as u64);
+            assert!(chunk.len <= config.max_size);
+            // Only the last chunk can be smaller than min_size
+            assert!(chunk.len >= config.min_size || i == chunks.len() - 1);
+            assert_eq!(chunk.hash, hash_piece(&data[offset..offset + chunk.len]));
+            offset += chunk.len;
+        }
+        assert_eq!(offset, data.len());
+        assert_eq!(config.chunks(Cursor::new(&data)).unwrap(), chunks);
+
+        let invalid = ChunkerConfig {
+            min_size: 4096,
+            avg_size: 1024,
+            max_size: 8192,
+        };
+        assert!(invalid.chunks(Cursor::new(&data)).is_err());
+    }
+    #[test]
+    fn test_stats() {
+        let data = random_bytes(512 * 1024, 2);
+        let ctree = ChunkTree::new(Cursor::new(&data), ChunkerConfig::default()).unwrap();
+        let stats = ctree.stats();
+
+        assert_eq!(stats.total_bytes, data.len() as u64);
+        assert!(stats.min_size <= stats.mean_size && stats.mean_size <= stats.max_size);
+        // Normalized chunking keeps the mean around the average size
+        assert!((4 * 1024..16 * 1024).contains(&stats.mean_size));
+        assert!(ChunkTree::new(Cursor::new(Vec::new()), ChunkerConfig::default()).is_err());
+    }
+    #[test]
+    fn test_insertion_stays_local() {
+        let data = random_bytes(512 * 1024, 3);
+        let mut edited = data.clone();
+        edited.insert(200 * 1024, b'X');
+
+        let config = ChunkerConfig::default();
+        let ctree = ChunkTree::new(Cursor::new(&data), config).unwrap();
+        let edited_tree = ChunkTree::new(Cursor::new(&edited), config).unwrap();
+        assert_ne!(ctree.root(), edited_tree.root());
+
+        let reuse = edited_tree.reuse(&ctree);
+        assert!(reuse.new_chunks <= 2);
+        assert_eq!(
+            reuse.reused_chunks + reuse.new_chunks,
+            edited_tree.chunks.len()
+        );
+        assert_eq!(reuse.reused_bytes + reuse.new_bytes, edited.len() as u64);
+
+        // With fixed-size pieces, every piece after the insertion changes
+        let fixed = |data: &[u8]| {
+            data.chunks(8 * 1024)
+                .map(hash_piece)
+                .collect::<HashSet<String>>()
+        };
+        let (before, after) = (fixed(&data), fixed(&edited));
+        assert!(after.difference(&before).count() > 30);
+    }
+    #[test]
+    fn test_diff() {
+        let data = random_bytes(512 * 1024, 3);
+        let config = ChunkerConfig::default();
+        let ctree = ChunkTree::new(Cursor::new(&data), config).unwrap();
+        assert!(ctree.diff(&ctree).is_empty());
+
+        // One inserted byte: the chunk around it is longer, the others don't move
+        let mut edited = data.clone();
+        edited.insert(200 * 1024, b'X');
+        let edited_tree = ChunkTree::new(Cursor::new(&edited), config).unwrap();
+        assert_eq!(ctree.chunks.len(), edited_tree.chunks.len());
+        let changed = ctree.diff(&edited_tree);
+        assert_eq!(changed.len(), 1);
+        let chunk = &edited_tree.chunks[changed[0]];
+        assert!((chunk.offset..chunk.offset + chunk.len as u64).contains(&(200 * 1024)));
+
+        // Only the ancestors of the changed chunk differ
+        let (levels, edited_levels) = (ctree.tree.levels(), edited_tree.tree.levels());
+        let changed_nodes = levels
+            .iter()
+            .zip(&edited_levels)
+            .map(|(a, b)| a.iter().zip(b).filter(|(x, y)| x != y).count())
+            .collect::<Vec<usize>>();
+        assert_eq!(changed_nodes, vec![1; levels.len()]);
+
+        // Inserted chunks shift the next ones
+        let mut edited = data.clone();
+        let inserted = random_bytes(64 * 1024, 4);
+        edited.splice(200 * 1024..200 * 1024, inserted);
+        let edited_tree = ChunkTree::new(Cursor::new(&edited), config).unwrap();
+        assert!(edited_tree.chunks.len() > ctree.chunks.len());
+        let changed = ctree.diff(&edited_tree);
+        assert!(changed.len() < 12);
+        let reused = edited_tree.chunks.len() - changed.len();
+        assert_eq!(reused, edited_tree.reuse(&ctree).reused_chunks);
+    }
+}
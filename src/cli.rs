@@ -0,0 +1,314 @@
+// Command-line tool: builds trees from leaves (one per line) and issues or checks JSON proofs.
+use std::fs;
+use std::io::{BufRead, Write};
+
+use crate::hashers;
+use crate::kary_merkle_tree::{FillPolicy, KaryMerkleTree};
+use crate::merkle_tree::MerkleTreeDs;
+use crate::proof::{Encoding, Proof};
+
+// The hashers listed are the ones compiled in
+pub fn usage() -> String {
+    format!(
+        "\
+Usage: merkletree_in_rust [OPTIONS] <COMMAND>
+
+Options can come before or after the command.
+
+Commands:
+  root [FILE]                          Print the root of the leaves in FILE (or stdin)
+  proof <LEAF> | --index N             Print the JSON proof of a leaf
+  verify --root R --proof FILE <LEAF>  Check a JSON proof (FILE can be -) against a trusted root
+  show                                 Render the tree
+
+Options:
+  --leaves FILE    Read the leaves from FILE instead of stdin, one per line
+  --hasher NAME    {}
+  --odd POLICY     What to do with the last node of odd levels: duplicate (default) or truncate
+  --encoding ENC   Encoding of the hashes: hex (default), 0x or base64
+  -h, --help       Print this message
+
+verify takes the hasher and odd-node policy from the proof: --hasher and --odd, when given,
+must match them.
+",
+        hashers::NAMES
+            .join(", ")
+            .replacen("keccak256", "keccak256 (default)", 1)
+    )
+}
+
+struct Options {
+    leaves: Option<String>,
+    // None when not given, so verify can tell a flag from the default
+    hasher: Option<String>,
+    odd: Option<FillPolicy>,
+    encoding: Encoding,
+    index: Option<usize>,
+    root: Option<String>,
+    proof: Option<String>,
+    positionals: Vec<String>,
+    help: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            leaves: None,
+            hasher: None,
+            odd: None,
+            encoding: Encoding::Hex,
+            index: None,
+            root: None,
+            proof: None,
+            positionals: Vec::new(),
+            help: false,
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or(format!("Missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--leaves" => options.leaves = Some(value()?),
+                "--hasher" => options.hasher = Some(value()?),
+                "--odd" => options.odd = Some(FillPolicy::from_name(&value()?)?),
+                "--encoding" => options.encoding = Encoding::from_name(&value()?)?,
+                "--index" => {
+                    options.index = Some(value()?.parse::<usize>().map_err(|e| e.to_string())?)
+                }
+                "--root" => options.root = Some(value()?),
+                "--proof" => options.proof = Some(value()?),
+                "-h" | "--help" => options.help = true,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}")),
+                _ => options.positionals.push(arg.clone()),
+            }
+        }
+        Ok(options)
+    }
+
+    fn hasher(&self) -> &str {
+        self.hasher.as_deref().unwrap_or("keccak256")
+    }
+
+    fn odd(&self) -> FillPolicy {
+        self.odd.unwrap_or(FillPolicy::DuplicateLast)
+    }
+
+    fn read_leaves(
+        &self,
+        path: Option<&String>,
+        stdin: &mut dyn BufRead,
+    ) -> Result<Vec<String>, String> {
+        let leaves = match path.or(self.leaves.as_ref()) {
+            Some(path) if path != "-" => fs::read_to_string(path)
+                .map_err(|e| format!("{path}: {e}"))?
+                .lines()
+                .map(|s| s.to_string())
+                .collect(),
+            _ => stdin
+                .lines()
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(|e| e.to_string())?,
+        };
+        if leaves.is_empty() {
+            return Err("No leaves".to_string());
+        }
+        Ok(leaves)
+    }
+}
+
+// Runs the command in args (without the program name).
+// An invalid proof is an error, so the process exits with a failure status.
+pub fn run(args: &[String], stdin: &mut dyn BufRead, stdout: &mut dyn Write) -> Result<(), String> {
+    let mut options = Options::parse(args)?;
+    if options.help {
+        return write!(stdout, "{}", usage()).map_err(|e| e.to_string());
+    }
+    // The command is the first argument that is not an option
+    if options.positionals.is_empty() {
+        return Err(usage());
+    }
+    let command = options.positionals.remove(0);
+    let hash_fn = hashers::by_name(options.hasher())?;
+
+    match command.as_str() {
+        "root" => {
+            let leaves = options.read_leaves(options.positionals.first(), stdin)?;
+            let ktree = KaryMerkleTree::new(&leaves, 2, options.odd(), &hash_fn)?;
+            writeln!(stdout, "{}", options.encoding.encode(&ktree.root)?).map_err(|e| e.to_string())
+        }
+        "proof" => {
+            let leaves = options.read_leaves(None, stdin)?;
+            let ktree = KaryMerkleTree::new(&leaves, 2, options.odd(), &hash_fn)?;
+            let index = match (options.index, options.positionals.first()) {
+                (Some(index), _) => index,
+                (None, Some(leaf)) => leaves
+                    .iter()
+                    .position(|x| x == leaf)
+                    .ok_or("Item not found".to_string())?,
+                (None, None) => return Err("Missing leaf or --index".to_string()),
+            };
+            let proof = Proof::from_tree(&ktree, index, options.hasher(), options.encoding)?;
+            writeln!(stdout, "{}", proof.to_json()).map_err(|e| e.to_string())
+        }
+        "verify" => {
+            let root = options.root.as_ref().ok_or("Missing --root".to_string())?;
+            let path = options
+                .proof
+                .as_ref()
+                .ok_or("Missing --proof".to_string())?;
+            let leaf = options
+                .positionals
+                .first()
+                .ok_or("Missing leaf".to_string())?;
+
+            let json = if path == "-" {
+                let mut json = String::new();
+                stdin.read_to_string(&mut json).map_err(|e| e.to_string())?;
+                json
+            } else {
+                fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?
+            };
+            let proof = Proof::from_json(&json)?;
+            if options.hasher.as_ref().is_some_and(|h| *h != proof.hasher) {
+                return Err(format!("The proof uses the hasher {}", proof.hasher));
+            }
+            if options.odd.is_some_and(|odd| odd != proof.odd) {
+                return Err("The proof uses another odd-node policy".to_string());
+            }
+
+            let trusted_root = options.encoding.decode(root)?;
+            let proof_root = proof.encoding.decode(&proof.root)?;
+            // Every check runs, the time doesn't tell which one failed
+            let valid = (proof.leaf == *leaf)
+                & hashers::constant_time_eq(proof_root.as_bytes(), trusted_root.as_bytes())
+                & proof.verify()?;
+            if !valid {
+                return Err("Invalid proof".to_string());
+            }
+            writeln!(stdout, "Valid proof").map_err(|e| e.to_string())
+        }
+        "show" => {
+            if options.odd() != FillPolicy::DuplicateLast {
+                return Err("show only renders trees with --odd duplicate".to_string());
+            }
+            let leaves = options.read_leaves(None, stdin)?;
+            let mtree = MerkleTreeDs::new_with_hasher(leaves, &hash_fn);
+            write!(stdout, "{mtree}").map_err(|e| e.to_string())
+        }
+        _ => Err(format!("Unknown command: {command}\n\n{}", usage())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+    use crate::slice_to_string;
+
+    fn run_with(args: &[&str], stdin: &str) -> Result<String, String> {
+        let args = args.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        let mut stdout = Vec::new();
+        run(&args, &mut stdin.as_bytes(), &mut stdout)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_root() {
+        let mtree = MerkleTreeVec::new(&slice_to_string!["D", "E", "F"], &keccak256);
+
+        let root = run_with(&["root"], "D\nE\nF\n").unwrap();
+        assert_eq!(root, format!("{}\n", mtree.root));
+
+        let root = run_with(&["root", "--encoding", "0x"], "D\nE\nF\n").unwrap();
+        assert_eq!(root, format!("0x{}\n", mtree.root));
+
+        let truncated = run_with(&["root", "--odd", "truncate"], "D\nE\nF\n").unwrap();
+        assert_ne!(truncated, format!("{}\n", mtree.root));
+
+        // Options before the command, as in the usage
+        let root = run_with(&["--encoding", "0x", "root"], "D\nE\nF\n").unwrap();
+        assert_eq!(root, format!("0x{}\n", mtree.root));
+    }
+    #[test]
+    fn test_proof_and_verify() {
+        let leaves = "D\nE\nF\nG\nH\n";
+        for encoding in ["hex", "base64"] {
+            let root = run_with(&["root", "--encoding", encoding], leaves).unwrap();
+            let by_leaf = run_with(&["proof", "G", "--encoding", encoding], leaves).unwrap();
+            let by_index =
+                run_with(&["proof", "--index", "3", "--encoding", encoding], leaves).unwrap();
+            assert_eq!(by_leaf, by_index);
+
+            let verify = [
+                "verify",
+                "--root",
+                root.trim(),
+                "--proof",
+                "-",
+                "--encoding",
+                encoding,
+            ];
+            let mut args = verify.to_vec();
+            args.push("G");
+            assert_eq!(run_with(&args, &by_leaf).unwrap(), "Valid proof\n");
+
+            let mut args = verify.to_vec();
+            args.push("D");
+            assert!(run_with(&args, &by_leaf).is_err());
+        }
+    }
+    #[test]
+    fn test_verify_flags() {
+        let leaves = "D\nE\nF\n";
+        let root = run_with(&["root"], leaves).unwrap();
+        let proof = run_with(&["proof", "E"], leaves).unwrap();
+        let verify = |flags: &[&str]| {
+            let mut args = vec!["verify", "--root", root.trim(), "--proof", "-"];
+            args.extend(flags);
+            args.push("E");
+            run_with(&args, &proof)
+        };
+
+        assert!(verify(&[]).is_ok());
+        assert!(verify(&["--hasher", "keccak256", "--odd", "duplicate"]).is_ok());
+        assert!(verify(&["--odd", "truncate"]).is_err());
+        #[cfg(feature = "sha256")]
+        assert_eq!(
+            verify(&["--hasher", "sha256"]),
+            Err("The proof uses the hasher keccak256".to_string())
+        );
+    }
+    #[test]
+    fn test_show() {
+        let show = run_with(&["show"], "D\n").unwrap();
+        assert_eq!(
+            show,
+            format!("{}", MerkleTreeDs::new(slice_to_string!["D"].to_vec()))
+        );
+        assert!(run_with(&["show", "--odd", "truncate"], "D\n").is_err());
+    }
+    #[test]
+    fn test_errors() {
+        assert!(run_with(&[], "").is_err());
+        assert!(run_with(&["root"], "").is_err());
+        assert!(run_with(&["root", "--hasher", "md5"], "D\n").is_err());
+        assert!(run_with(&["proof", "X"], "D\n").is_err());
+        assert!(run_with(&["frobnicate"], "D\n").is_err());
+        assert!(run_with(&["--help"], "").unwrap().starts_with("Usage"));
+        assert!(run_with(&["root", "-h"], "").unwrap().starts_with("Usage"));
+    }
+    #[test]
+    fn test_usage_hashers() {
+        let usage = usage();
+        for name in hashers::NAMES {
+            assert!(usage.contains(name));
+            assert!(hashers::by_name(name).is_ok());
+        }
+        #[cfg(not(feature = "blake3"))]
+        assert!(!usage.contains("blake3"));
+    }
+}
@@ -0,0 +1,313 @@
+// Fingerprint of a directory: a MerkleTreeDs whose leaves are the (relative path, content hash)
+// entries of every file, sorted by path. Two fingerprints of directories with the same number of
+// files are compared top-down, descending only into the subtrees whose hashes differ (see
+// MerkleTreeDs::diff), so identical parts are never visited. An added or removed file shifts every
+// later leaf: the trees don't line up any more and the entries are merged one by one instead.
+extern crate crypto;
+
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use self::crypto::digest::Digest;
+use crypto::sha3::Sha3;
+
+use crate::merkle_tree::MerkleTreeDs;
+
+pub struct DirectoryTree {
+    // (relative path with "/" separators, Keccak-256 of the content), sorted by path
+    pub entries: Vec<(String, String)>,
+    pub tree: MerkleTreeDs,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiffEntry {
+    Added(String),
+    Removed(String),
+    Modified(String),
+}
+
+// Same layout as the lines of `sha256sum`: the hash has a fixed length, so the path can't be
+// confused with it.
+pub fn encode_entry(path: &str, content_hash: &str) -> String {
+    format!("{content_hash}  {path}")
+}
+
+pub fn hash_file(path: impl AsRef<Path>) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut sha3 = Sha3::keccak256();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        sha3.input(&buf[..n]);
+    }
+    Ok(sha3.result_str())
+}
+
+impl DirectoryTree {
+    pub fn new(dir: impl AsRef<Path>) -> Result<DirectoryTree, String> {
+        let mut entries = Vec::new();
+        DirectoryTree::walk(dir.as_ref(), "", &mut entries)?;
+        DirectoryTree::from_entries(entries)
+    }
+
+    pub fn from_entries(mut entries: Vec<(String, String)>) -> Result<DirectoryTree, String> {
+        if entries.is_empty() {
+            return Err("Empty directory".to_string());
+        }
+        entries.sort();
+
+        let leaves = entries
+            .iter()
+            .map(|(path, hash)| encode_entry(path, hash))
+            .collect();
+
+        Ok(DirectoryTree {
+            entries,
+            tree: MerkleTreeDs::new(leaves),
+        })
+    }
+
+    pub fn root(&self) -> &str {
+        // new() never builds an empty tree
+        &self.tree.root.as_ref().unwrap().hash
+    }
+
+    // Files added, removed or modified in `other` compared to self. Logarithmic when only contents
+    // changed, linear in the number of files when files were added or removed.
+    pub fn diff(&self, other: &DirectoryTree) -> Vec<DiffEntry> {
+        self.diff_visiting(other).0
+    }
+
+    // The diff and the number of entries compared
+    fn diff_visiting(&self, other: &DirectoryTree) -> (Vec<DiffEntry>, usize) {
+        let indices = match self.tree.diff(&other.tree) {
+            Ok(indices) => indices,
+            // Trees of different sizes don't line up
+            Err(_) => return self.merge_diff(other),
+        };
+
+        let mut diff = Vec::new();
+        for &index in &indices {
+            if let Some((path, hash)) = self.entries.get(index) {
+                match other.find(path) {
+                    None => diff.push(DiffEntry::Removed(path.clone())),
+                    Some(other_hash) if other_hash != hash => {
+                        diff.push(DiffEntry::Modified(path.clone()))
+                    }
+                    // Same file, shifted by an addition or a removal
+                    Some(_) => {}
+                }
+            }
+            if let Some((path, _)) = other.entries.get(index) {
+                if self.find(path).is_none() {
+                    diff.push(DiffEntry::Added(path.clone()));
+                }
+            }
+        }
+        diff.sort();
+        diff.dedup();
+        (diff, indices.len())
+    }
+
+    // Walks both sorted lists of entries once
+    fn merge_diff(&self, other: &DirectoryTree) -> (Vec<DiffEntry>, usize) {
+        let mut diff = Vec::new();
+        let mut entries = self.entries.iter().peekable();
+        let mut other_entries = other.entries.iter().peekable();
+
+        loop {
+            match (entries.peek(), other_entries.peek()) {
+                (Some((path, hash)), Some((other_path, other_hash))) => {
+                    match path.cmp(other_path) {
+                        Ordering::Less => {
+                            diff.push(DiffEntry::Removed(path.clone()));
+                            entries.next();
+                        }
+                        Ordering::Greater => {
+                            diff.push(DiffEntry::Added(other_path.clone()));
+                            other_entries.next();
+                        }
+                        Ordering::Equal => {
+                            if hash != other_hash {
+                                diff.push(DiffEntry::Modified(path.clone()));
+                            }
+                            entries.next();
+                            other_entries.next();
+                        }
+                    }
+                }
+                (Some((path, _)), None) => {
+                    diff.push(DiffEntry::Removed(path.clone()));
+                    entries.next();
+                }
+                (None, Some((other_path, _))) => {
+                    diff.push(DiffEntry::Added(other_path.clone()));
+                    other_entries.next();
+                }
+                (None, None) => break,
+            }
+        }
+        diff.sort();
+        (diff, self.entries.len().max(other.entries.len()))
+    }
+
+    fn find(&self, path: &str) -> Option<&String> {
+        self.entries
+            .binary_search_by(|(p, _)| p.as_str().cmp(path))
+            .ok()
+            .map(|i| &self.entries[i].1)
+    }
+
+    fn walk(dir: &Path, prefix: &str, entries: &mut Vec<(String, String)>) -> Result<(), String> {
+        for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| format!("Invalid file name: {:?}", name))?;
+            let relative_path = format!("{prefix}{name}");
+            let file_type = entry.file_type().map_err(|e| e.to_string())?;
+
+            // Symbolic links are not followed
+            if file_type.is_dir() {
+                DirectoryTree::walk(&entry.path(), &format!("{relative_path}/"), entries)?;
+            } else if file_type.is_file() {
+                entries.push((relative_path, hash_file(entry.path())?));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::keccak256;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "merkletree_in_rust_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn entries(n: usize) -> Vec<(String, String)> {
+        (0..n)
+            .map(|i| (format!("file_{i:02}"), keccak256(&i.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_new() {
+        let dir = temp_dir("directory_new", &[("b/c.txt", "C"), ("a.txt", "A")]);
+        let dtree = DirectoryTree::new(&dir).unwrap();
+
+        assert_eq!(
+            dtree.entries,
+            vec![
+                ("a.txt".to_string(), keccak256("A")),
+                ("b/c.txt".to_string(), keccak256("C")),
+            ]
+        );
+        let cmp = MerkleTreeDs::new(vec![
+            encode_entry("a.txt", &keccak256("A")),
+            encode_entry("b/c.txt", &keccak256("C")),
+        ]);
+        assert_eq!(dtree.root(), cmp.root.unwrap().hash);
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(DirectoryTree::from_entries(Vec::new()).is_err());
+    }
+    #[test]
+    fn test_diff_modified() {
+        let dtree = DirectoryTree::from_entries(entries(13)).unwrap();
+
+        let mut modified = entries(13);
+        modified[4].1 = keccak256("changed");
+        modified[12].1 = keccak256("changed");
+        let other = DirectoryTree::from_entries(modified).unwrap();
+
+        assert_eq!(
+            dtree.diff(&other),
+            vec![
+                DiffEntry::Modified("file_04".to_string()),
+                DiffEntry::Modified("file_12".to_string()),
+            ]
+        );
+        assert!(dtree.diff(&dtree).is_empty());
+    }
+    #[test]
+    fn test_diff_added_removed() {
+        let dtree = DirectoryTree::from_entries(entries(8)).unwrap();
+
+        let mut changed = entries(8);
+        changed.remove(2);
+        changed.push(("file_99".to_string(), keccak256("new")));
+        let other = DirectoryTree::from_entries(changed).unwrap();
+        assert_eq!(
+            dtree.diff(&other),
+            vec![
+                DiffEntry::Added("file_99".to_string()),
+                DiffEntry::Removed("file_02".to_string()),
+            ]
+        );
+
+        // Different sizes
+        let other = DirectoryTree::from_entries(entries(9)).unwrap();
+        assert_eq!(
+            dtree.diff(&other),
+            vec![DiffEntry::Added("file_08".to_string())]
+        );
+    }
+    #[test]
+    fn test_diff_visited() {
+        let n = 1024;
+        let dtree = DirectoryTree::from_entries(entries(n)).unwrap();
+
+        let mut modified = entries(n);
+        modified[700].1 = keccak256("changed");
+        let (diff, visited) = dtree.diff_visiting(&DirectoryTree::from_entries(modified).unwrap());
+        assert_eq!(diff, vec![DiffEntry::Modified("file_700".to_string())]);
+        assert_eq!(visited, 1);
+
+        // The later leaves are shifted, every entry is compared
+        let mut added = entries(n);
+        added.push(("file_0500a".to_string(), keccak256("new")));
+        let (diff, visited) = dtree.diff_visiting(&DirectoryTree::from_entries(added).unwrap());
+        assert_eq!(diff, vec![DiffEntry::Added("file_0500a".to_string())]);
+        assert_eq!(visited, n + 1);
+    }
+    #[test]
+    fn test_diff_directories() {
+        let files = [("a.txt", "A"), ("b/c.txt", "C"), ("b/d.txt", "D")];
+        let dir = temp_dir("directory_diff_a", &files);
+        let other_dir = temp_dir("directory_diff_b", &files);
+        fs::write(other_dir.join("b/d.txt"), "changed").unwrap();
+
+        let dtree = DirectoryTree::new(&dir).unwrap();
+        let other = DirectoryTree::new(&other_dir).unwrap();
+        assert_ne!(dtree.root(), other.root());
+        assert_eq!(
+            dtree.diff(&other),
+            vec![DiffEntry::Modified("b/d.txt".to_string())]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&other_dir).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+// Graphviz DOT and Mermaid renderings of MerkleTreeDs and MerkleTreeVec, drawn bottom-up like
+// the diagram of the README. Nodes are labelled with the first characters of their hash.
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::merkle_tree::MerkleTreeDs;
+use crate::merkle_tree_vec::MerkleTreeVec;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportOptions {
+    // Add the original values under the hashed leaves (when they are known)
+    pub leaf_values: bool,
+    // Draw the copies made for the last node of odd levels
+    pub duplicates: bool,
+    // Highlight the path from this leaf to the root and the siblings of its proof
+    pub proof_index: Option<usize>,
+    pub hash_prefix: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            leaf_values: false,
+            duplicates: false,
+            proof_index: None,
+            hash_prefix: 5,
+        }
+    }
+}
+
+// The hashes of a tree, bottom-up, without the duplicated nodes
+pub struct TreeGraph {
+    pub levels: Vec<Vec<String>>,
+    pub leaf_values: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Hash,
+    Duplicate,
+    Value,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Highlight {
+    None,
+    Path,
+    Sibling,
+}
+
+struct GraphNode {
+    id: String,
+    label: String,
+    kind: Kind,
+    highlight: Highlight,
+}
+
+impl TreeGraph {
+    pub fn from_vec_tree(tree: &MerkleTreeVec) -> TreeGraph {
+        TreeGraph {
+            levels: tree.levels(),
+            leaf_values: Some(tree.initial_leaves.clone()),
+        }
+    }
+
+    // MerkleTreeDs doesn't keep the original values, set leaf_values to draw them.
+    pub fn from_ds_tree(tree: &MerkleTreeDs) -> TreeGraph {
+        TreeGraph {
+            levels: tree.levels(),
+            leaf_values: None,
+        }
+    }
+
+    pub fn to_dot(&self, options: &ExportOptions) -> String {
+        let (nodes, edges) = self.graph(options);
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph merkle_tree {{").unwrap();
+        writeln!(dot, "    rankdir=BT;").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for node in &nodes {
+            let mut attributes = vec![format!("label=\"{}\"", escape_dot(&node.label))];
+            match node.kind {
+                Kind::Duplicate => attributes.push("style=dashed".to_string()),
+                Kind::Value => attributes.push("shape=ellipse".to_string()),
+                Kind::Hash => {}
+            }
+            match node.highlight {
+                Highlight::Path => attributes.push("color=blue, penwidth=2".to_string()),
+                Highlight::Sibling => attributes.push("color=orange, penwidth=2".to_string()),
+                Highlight::None => {}
+            }
+            writeln!(dot, "    {} [{}];", node.id, attributes.join(", ")).unwrap();
+        }
+        for (from, to) in &edges {
+            writeln!(dot, "    {from} -> {to};").unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    pub fn to_mermaid(&self, options: &ExportOptions) -> String {
+        let (nodes, edges) = self.graph(options);
+        let mut mermaid = String::new();
+
+        writeln!(mermaid, "flowchart BT").unwrap();
+        for node in &nodes {
+            let label = node.label.replace('"', "#quot;");
+            match node.kind {
+                Kind::Value => writeln!(mermaid, "    {}((\"{}\"))", node.id, label).unwrap(),
+                _ => writeln!(mermaid, "    {}[\"{}\"]", node.id, label).unwrap(),
+            }
+        }
+        for (from, to) in &edges {
+            writeln!(mermaid, "    {from} --> {to}").unwrap();
+        }
+
+        let classes = [
+            ("duplicate", "stroke-dasharray: 5 5"),
+            ("path", "stroke:#00f,stroke-width:3px"),
+            ("sibling", "stroke:#f90,stroke-width:3px"),
+        ];
+        for (class, style) in classes {
+            let ids = nodes
+                .iter()
+                .filter(|node| match class {
+                    "duplicate" => node.kind == Kind::Duplicate,
+                    "path" => node.highlight == Highlight::Path,
+                    _ => node.highlight == Highlight::Sibling,
+                })
+                .map(|node| node.id.as_str())
+                .collect::<Vec<&str>>();
+            if !ids.is_empty() {
+                writeln!(mermaid, "    classDef {class} {style}").unwrap();
+                writeln!(mermaid, "    class {} {class}", ids.join(",")).unwrap();
+            }
+        }
+        mermaid
+    }
+
+    fn graph(&self, options: &ExportOptions) -> (Vec<GraphNode>, Vec<(String, String)>) {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        let prefix = |hash: &str| hash.chars().take(options.hash_prefix).collect::<String>();
+        let highlight = |level: usize, index: usize| match options.proof_index {
+            Some(leaf) if leaf >> level == index => Highlight::Path,
+            Some(leaf) if (leaf >> level) ^ 1 == index => Highlight::Sibling,
+            _ => Highlight::None,
+        };
+
+        for (level, hashes) in self.levels.iter().enumerate() {
+            let is_top = level + 1 == self.levels.len();
+            for (index, hash) in hashes.iter().enumerate() {
+                nodes.push(GraphNode {
+                    id: format!("n{level}_{index}"),
+                    label: prefix(hash),
+                    kind: Kind::Hash,
+                    highlight: highlight(level, index),
+                });
+                if !is_top {
+                    edges.push((
+                        format!("n{level}_{index}"),
+                        format!("n{}_{}", level + 1, index / 2),
+                    ));
+                }
+            }
+
+            // "Balance" the tree -> Duplicate if there is no right leaf
+            let last = hashes.len() - 1;
+            if !is_top && last.is_multiple_of(2) {
+                let (index, parent) = (last + 1, format!("n{}_{}", level + 1, last / 2));
+                if options.duplicates {
+                    nodes.push(GraphNode {
+                        id: format!("d{level}_{index}"),
+                        label: format!("{} (copy)", prefix(&hashes[last])),
+                        kind: Kind::Duplicate,
+                        highlight: highlight(level, index),
+                    });
+                    edges.push((format!("d{level}_{index}"), parent));
+                } else {
+                    // Both children are the same node
+                    edges.push((format!("n{level}_{last}"), parent));
+                }
+            }
+        }
+
+        if let (true, Some(values)) = (options.leaf_values, &self.leaf_values) {
+            for (index, value) in values.iter().enumerate() {
+                nodes.push(GraphNode {
+                    id: format!("v{index}"),
+                    label: value.clone(),
+                    kind: Kind::Value,
+                    highlight: Highlight::None,
+                });
+                edges.push((format!("v{index}"), format!("n0_{index}")));
+            }
+        }
+        (nodes, edges)
+    }
+}
+
+fn escape_dot(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::keccak256;
+    use crate::{slice_to_string, vec_to_string};
+
+    #[test]
+    fn test_same_graph_for_both_trees() {
+        let leaves = vec_to_string!["D", "E", "F", "G", "H"];
+        let mtree_vec = MerkleTreeVec::new(&leaves, &keccak256);
+        let mtree = MerkleTreeDs::new(leaves);
+
+        let from_vec = TreeGraph::from_vec_tree(&mtree_vec);
+        let from_ds = TreeGraph::from_ds_tree(&mtree);
+        assert_eq!(from_ds.levels, from_vec.levels);
+
+        let options = ExportOptions {
+            duplicates: true,
+            ..ExportOptions::default()
+        };
+        assert_eq!(from_ds.to_dot(&options), from_vec.to_dot(&options));
+        assert_eq!(from_ds.to_mermaid(&options), from_vec.to_mermaid(&options));
+    }
+    #[test]
+    fn test_to_mermaid_single_leaf() {
+        let mtree = MerkleTreeVec::new(&slice_to_string!["D"], &keccak256);
+        let options = ExportOptions {
+            leaf_values: true,
+            duplicates: true,
+            proof_index: Some(0),
+            hash_prefix: 5,
+        };
+
+        let expected = "\
+flowchart BT
+    n0_0[\"6c3fd\"]
+    d0_1[\"6c3fd (copy)\"]
+    n1_0[\"5bba9\"]
+    v0((\"D\"))
+    n0_0 --> n1_0
+    d0_1 --> n1_0
+    v0 --> n0_0
+    classDef duplicate stroke-dasharray: 5 5
+    class d0_1 duplicate
+    classDef path stroke:#00f,stroke-width:3px
+    class n0_0,n1_0 path
+    classDef sibling stroke:#f90,stroke-width:3px
+    class d0_1 sibling
+";
+        assert_eq!(
+            TreeGraph::from_vec_tree(&mtree).to_mermaid(&options),
+            expected
+        );
+    }
+    #[test]
+    fn test_to_dot() {
+        let mtree = MerkleTreeVec::new(&slice_to_string!["D", "E", "F", "say \"hi\""], &keccak256);
+        let options = ExportOptions {
+            leaf_values: true,
+            proof_index: Some(2),
+            ..ExportOptions::default()
+        };
+        let dot = TreeGraph::from_vec_tree(&mtree).to_dot(&options);
+
+        assert!(dot.starts_with("digraph merkle_tree {\n    rankdir=BT;\n"));
+        assert!(dot.contains("v3 [label=\"say \\\"hi\\\"\", shape=ellipse];"));
+        assert!(dot.contains(&format!(
+            "n0_2 [label=\"{}\", color=blue, penwidth=2];",
+            &keccak256("F")[..5]
+        )));
+        assert!(dot.contains("n1_0 [label=") && dot.contains("color=orange"));
+        assert!(dot.contains("n0_3 -> n1_1;"));
+        // No odd level, nothing duplicated
+        assert!(!dot.contains("dashed"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,327 @@
+// C ABI around MerkleTreeVec, for firmware and C/C++ services checking the proofs of this crate.
+// The header include/merkletree.h is generated from this file by cbindgen (see build.rs).
+//
+// Trees are opaque handles, created by mt_tree_new and released with mt_tree_free. Nothing else
+// is allocated for the caller: roots and proofs are written into caller-provided buffers as
+// NUL-terminated hex strings, a proof being the hex hashes of the siblings one after the other.
+// Every function returns an MtStatus and writes its results through out-pointers.
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use std::slice;
+
+use crate::hashers;
+use crate::kary_merkle_tree::{kary_proof_from_levels, FillPolicy};
+use crate::merkle_tree_vec::{verify_proof, MerkleTreeVec};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MtStatus {
+    Ok = 0,
+    NullPointer,
+    InvalidUtf8,
+    EmptyTree,
+    UnknownHasher,
+    IndexOutOfBounds,
+    // The buffer was left untouched, `written` holds the length needed (without the NUL)
+    BufferTooSmall,
+    // The length of the proof is not a multiple of the length of the root
+    MalformedProof,
+}
+
+// Opaque to C. Every level is hashed once in mt_tree_new, proofs are read from them.
+pub struct MtTree {
+    // levels[0] holds the hashed leaves and the last level the root
+    levels: Vec<Vec<String>>,
+}
+
+// NULL selects keccak256
+unsafe fn hash_fn_by_name(name: *const c_char) -> Result<fn(&str) -> String, MtStatus> {
+    if name.is_null() {
+        return Ok(hashers::keccak256);
+    }
+    let name = CStr::from_ptr(name)
+        .to_str()
+        .map_err(|_| MtStatus::InvalidUtf8)?;
+    hashers::by_name(name).map_err(|_| MtStatus::UnknownHasher)
+}
+
+unsafe fn str_from_buffer<'a>(data: *const u8, len: usize) -> Result<&'a str, MtStatus> {
+    if data.is_null() && len > 0 {
+        return Err(MtStatus::NullPointer);
+    }
+    let bytes = match len {
+        0 => &[],
+        _ => slice::from_raw_parts(data, len),
+    };
+    std::str::from_utf8(bytes).map_err(|_| MtStatus::InvalidUtf8)
+}
+
+// Copies s and a NUL into out, if it fits
+unsafe fn write_str(s: &str, out: *mut c_char, out_len: usize, written: *mut usize) -> MtStatus {
+    if out.is_null() || written.is_null() {
+        return MtStatus::NullPointer;
+    }
+    *written = s.len();
+    if s.len() >= out_len {
+        return MtStatus::BufferTooSmall;
+    }
+    ptr::copy_nonoverlapping(s.as_ptr(), out as *mut u8, s.len());
+    *out.add(s.len()) = 0;
+    MtStatus::Ok
+}
+
+/// Builds a tree from `count` leaves, leaf `i` being the `leaf_lens[i]` bytes (UTF-8) at `leaves[i]`.
+/// `hasher` is the name of a hash function of the hashers module, or NULL for keccak256.
+///
+/// # Safety
+/// `leaves` and `leaf_lens` point to `count` elements, each leaf to `leaf_lens[i]` readable bytes,
+/// `hasher` is NULL or a NUL-terminated string and `tree` is writable.
+#[no_mangle]
+pub unsafe extern "C" fn mt_tree_new(
+    leaves: *const *const u8,
+    leaf_lens: *const usize,
+    count: usize,
+    hasher: *const c_char,
+    tree: *mut *mut MtTree,
+) -> MtStatus {
+    if tree.is_null() || leaves.is_null() || leaf_lens.is_null() {
+        return MtStatus::NullPointer;
+    }
+    if count == 0 {
+        return MtStatus::EmptyTree;
+    }
+    let hash_fn = match hash_fn_by_name(hasher) {
+        Ok(hash_fn) => hash_fn,
+        Err(status) => return status,
+    };
+
+    let buffers = slice::from_raw_parts(leaves, count);
+    let lens = slice::from_raw_parts(leaf_lens, count);
+    let mut values = Vec::with_capacity(count);
+    for (&data, &len) in buffers.iter().zip(lens) {
+        match str_from_buffer(data, len) {
+            Ok(value) => values.push(value.to_string()),
+            Err(status) => return status,
+        }
+    }
+
+    let levels = MerkleTreeVec::new(&values, &hash_fn).levels();
+    *tree = Box::into_raw(Box::new(MtTree { levels }));
+    MtStatus::Ok
+}
+
+/// Releases a tree of mt_tree_new. NULL is ignored.
+///
+/// # Safety
+/// `tree` is NULL or a tree of mt_tree_new that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn mt_tree_free(tree: *mut MtTree) {
+    if !tree.is_null() {
+        drop(Box::from_raw(tree));
+    }
+}
+
+/// Number of leaves of the tree, 0 for NULL.
+///
+/// # Safety
+/// `tree` is NULL or a live tree of mt_tree_new.
+#[no_mangle]
+pub unsafe extern "C" fn mt_tree_leaves_count(tree: *const MtTree) -> usize {
+    match tree.as_ref() {
+        Some(tree) => tree.levels[0].len(),
+        None => 0,
+    }
+}
+
+/// Writes the hex root and a NUL into `root` (`root_len` bytes), its length into `written`.
+///
+/// # Safety
+/// `tree` is a live tree of mt_tree_new, `root` has `root_len` writable bytes and `written` is
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn mt_tree_root(
+    tree: *const MtTree,
+    root: *mut c_char,
+    root_len: usize,
+    written: *mut usize,
+) -> MtStatus {
+    match tree.as_ref() {
+        Some(tree) => write_str(
+            &tree.levels[tree.levels.len() - 1][0],
+            root,
+            root_len,
+            written,
+        ),
+        None => MtStatus::NullPointer,
+    }
+}
+
+/// Writes the proof of the leaf at `index` and a NUL into `proof` (`proof_len` bytes), its length
+/// into `written`. The proof is the concatenation of the hex hashes of the siblings, bottom-up.
+///
+/// # Safety
+/// `tree` is a live tree of mt_tree_new, `proof` has `proof_len` writable bytes and `written` is
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn mt_tree_proof(
+    tree: *const MtTree,
+    index: usize,
+    proof: *mut c_char,
+    proof_len: usize,
+    written: *mut usize,
+) -> MtStatus {
+    let tree = match tree.as_ref() {
+        Some(tree) => tree,
+        None => return MtStatus::NullPointer,
+    };
+    // Same proofs as MerkleTreeVec
+    match kary_proof_from_levels(&tree.levels, index, 2, FillPolicy::DuplicateLast) {
+        Ok(kary_proof) => write_str(
+            &kary_proof.siblings.concat().concat(),
+            proof,
+            proof_len,
+            written,
+        ),
+        Err(_) => MtStatus::IndexOutOfBounds,
+    }
+}
+
+/// Checks that the leaf (`leaf_len` bytes at `leaf`) is at `index` in the tree of `root`, with a
+/// proof of mt_tree_proof. `valid` is only written when MT_STATUS_OK is returned.
+///
+/// # Safety
+/// `root` and `proof` are NUL-terminated strings, `leaf` has `leaf_len` readable bytes, `hasher`
+/// is NULL or a NUL-terminated string and `valid` is writable.
+#[no_mangle]
+pub unsafe extern "C" fn mt_verify_proof(
+    root: *const c_char,
+    leaf: *const u8,
+    leaf_len: usize,
+    index: usize,
+    proof: *const c_char,
+    hasher: *const c_char,
+    valid: *mut bool,
+) -> MtStatus {
+    if root.is_null() || proof.is_null() || valid.is_null() {
+        return MtStatus::NullPointer;
+    }
+    let hash_fn = match hash_fn_by_name(hasher) {
+        Ok(hash_fn) => hash_fn,
+        Err(status) => return status,
+    };
+    let (root, proof, leaf) = match (
+        CStr::from_ptr(root).to_str(),
+        CStr::from_ptr(proof).to_str(),
+        str_from_buffer(leaf, leaf_len),
+    ) {
+        (Ok(root), Ok(proof), Ok(leaf)) => (root, proof, leaf),
+        (_, _, Err(status)) => return status,
+        _ => return MtStatus::InvalidUtf8,
+    };
+
+    // Every sibling has the length of the root
+    if root.is_empty() || !proof.len().is_multiple_of(root.len()) || !proof.is_ascii() {
+        return MtStatus::MalformedProof;
+    }
+    let siblings = proof
+        .as_bytes()
+        .chunks(root.len())
+        .map(|hash| String::from_utf8_lossy(hash).into_owned())
+        .collect::<Vec<String>>();
+
+    *valid = verify_proof(root, &siblings, leaf, index, &hash_fn);
+    MtStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tests/ffi/test_ffi.c, compiled by build.rs with the "ffi-test" feature
+    #[cfg(feature = "ffi-test")]
+    #[link(name = "ffi_test", kind = "static")]
+    extern "C" {
+        fn run_ffi_tests(expected_root: *const c_char) -> i32;
+    }
+
+    #[cfg(feature = "ffi-test")]
+    #[test]
+    fn test_c_program() {
+        use crate::merkle_tree_vec::keccak256;
+        use crate::slice_to_string;
+
+        let mtree = MerkleTreeVec::new(&slice_to_string!["D", "E", "F", "G", "H"], &keccak256);
+        let expected_root = std::ffi::CString::new(mtree.root).unwrap();
+        // Number of failed checks
+        assert_eq!(unsafe { run_ffi_tests(expected_root.as_ptr()) }, 0);
+    }
+    #[test]
+    fn test_header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/merkletree.h"));
+        let committed = include_str!("../include/merkletree.h");
+        assert!(
+            generated == committed,
+            "include/merkletree.h is outdated, copy it from {}",
+            env!("OUT_DIR")
+        );
+    }
+    #[test]
+    fn test_errors() {
+        let mut tree = ptr::null_mut();
+        let invalid = [0xffu8];
+        let leaves = [b"D".as_ptr(), invalid.as_ptr()];
+        let lens = [1, 1];
+        unsafe {
+            assert_eq!(
+                mt_tree_new(leaves.as_ptr(), lens.as_ptr(), 0, ptr::null(), &mut tree),
+                MtStatus::EmptyTree
+            );
+            assert_eq!(
+                mt_tree_new(leaves.as_ptr(), lens.as_ptr(), 2, ptr::null(), &mut tree),
+                MtStatus::InvalidUtf8
+            );
+            assert_eq!(
+                mt_tree_new(
+                    leaves.as_ptr(),
+                    lens.as_ptr(),
+                    1,
+                    c"md5".as_ptr(),
+                    &mut tree
+                ),
+                MtStatus::UnknownHasher
+            );
+            assert!(tree.is_null());
+
+            assert_eq!(
+                mt_tree_new(leaves.as_ptr(), lens.as_ptr(), 1, ptr::null(), &mut tree),
+                MtStatus::Ok
+            );
+            let mut buf = [0 as c_char; 8];
+            let mut written = 0;
+            assert_eq!(
+                mt_tree_root(tree, buf.as_mut_ptr(), buf.len(), &mut written),
+                MtStatus::BufferTooSmall
+            );
+            assert_eq!(written, 64);
+            assert_eq!(
+                mt_tree_proof(tree, 1, buf.as_mut_ptr(), buf.len(), &mut written),
+                MtStatus::IndexOutOfBounds
+            );
+            mt_tree_free(tree);
+
+            let mut valid = false;
+            assert_eq!(
+                mt_verify_proof(
+                    c"abcd".as_ptr(),
+                    leaves[0],
+                    1,
+                    0,
+                    c"abc".as_ptr(),
+                    ptr::null(),
+                    &mut valid
+                ),
+                MtStatus::MalformedProof
+            );
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// Ready-made hash functions for MerkleTreeVec::new and MerkleTreeDs::new_with_hasher.
+// Like keccak256, every hasher takes the leaf (or the concatenation of two hex hashes)
+// as a string and returns the lowercase hex encoding of the digest.
+// Each one is behind its own cargo feature, Keccak-256 is always available.
+#[cfg(feature = "std")]
+extern crate crypto;
+
+use alloc::format;
+use alloc::string::String;
+
+#[cfg(any(feature = "sha256", feature = "sha3-256", feature = "blake2b"))]
+use self::crypto::digest::Digest;
+
+pub use crate::merkle_tree_vec::keccak256;
+
+#[cfg(feature = "sha256")]
+pub fn sha256(s: &str) -> String {
+    let mut sha256 = crypto::sha2::Sha256::new();
+    sha256.input_str(s);
+    sha256.result_str()
+}
+
+// As used by Bitcoin: SHA-256 over the raw bytes of the first SHA-256 digest.
+#[cfg(feature = "sha256")]
+pub fn double_sha256(s: &str) -> String {
+    let mut first = [0u8; 32];
+    let mut sha256 = crypto::sha2::Sha256::new();
+    sha256.input_str(s);
+    sha256.result(&mut first);
+
+    let mut sha256 = crypto::sha2::Sha256::new();
+    sha256.input(&first);
+    sha256.result_str()
+}
+
+#[cfg(feature = "sha3-256")]
+pub fn sha3_256(s: &str) -> String {
+    let mut sha3 = crypto::sha3::Sha3::sha3_256();
+    sha3.input_str(s);
+    sha3.result_str()
+}
+
+// BLAKE2b with a 32 byte digest, the same size as the other hashers.
+#[cfg(feature = "blake2b")]
+pub fn blake2b256(s: &str) -> String {
+    let mut blake2b = crypto::blake2b::Blake2b::new(32);
+    blake2b.input_str(s);
+    blake2b.result_str()
+}
+
+#[cfg(feature = "blake3")]
+pub fn blake3(s: &str) -> String {
+    ::blake3::hash(s.as_bytes()).to_hex().to_string()
+}
+
+// Names accepted by by_name, only the hashers compiled in
+pub const NAMES: &[&str] = &[
+    "keccak256",
+    #[cfg(feature = "sha256")]
+    "sha256",
+    #[cfg(feature = "sha256")]
+    "double_sha256",
+    #[cfg(feature = "sha3-256")]
+    "sha3_256",
+    #[cfg(feature = "blake2b")]
+    "blake2b256",
+    #[cfg(feature = "blake3")]
+    "blake3",
+];
+
+// Looks up a hasher by the name of its function, e.g. for command-line flags.
+pub fn by_name(name: &str) -> Result<fn(&str) -> String, String> {
+    match name {
+        "keccak256" => Ok(keccak256),
+        #[cfg(feature = "sha256")]
+        "sha256" => Ok(sha256),
+        #[cfg(feature = "sha256")]
+        "double_sha256" => Ok(double_sha256),
+        #[cfg(feature = "sha3-256")]
+        "sha3_256" => Ok(sha3_256),
+        #[cfg(feature = "blake2b")]
+        "blake2b256" => Ok(blake2b256),
+        #[cfg(feature = "blake3")]
+        "blake3" => Ok(blake3),
+        _ => Err(format!("Unknown hasher: {name}")),
+    }
+}
+
+// Equality of two digests (or roots) in constant time: every byte is compared whatever the first
+// difference, so the time taken doesn't tell a client how much of a forged root was right.
+// Only a different length, which is public (the size of the digest), returns early.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+    // Keeps the compiler from turning the fold back into a loop that exits early
+    core::hint::black_box(diff) == 0
+}
+
+// The known answers are the digests of "abc" and the roots of REFERENCE_LEAVES
+// built with both tree types.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::MerkleTreeDs;
+    use crate::merkle_tree_vec::MerkleTreeVec;
+
+    const REFERENCE_LEAVES: [&str; 5] = ["D", "E", "F", "G", "H"];
+
+    fn check_roots(hash_fn: &(dyn Fn(&str) -> String + Sync), root: &str) {
+        let leaves = REFERENCE_LEAVES.map(|s| s.to_string());
+
+        let mtree_vec = MerkleTreeVec::new(&leaves, hash_fn);
+        let mtree = MerkleTreeDs::new_with_hasher(leaves.to_vec(), hash_fn);
+
+        assert_eq!(mtree_vec.root, root);
+        assert_eq!(mtree.root.unwrap().hash, root);
+    }
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            keccak256("abc"),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        check_roots(
+            &keccak256,
+            "e292bbde8e532949a367ebdf77bd8042f779f6c8eebcc0ea0deff00a7798f479",
+        );
+    }
+    #[test]
+    fn test_constant_time_eq() {
+        let root = keccak256("D");
+        assert!(constant_time_eq(root.as_bytes(), keccak256("D").as_bytes()));
+        assert!(!constant_time_eq(
+            root.as_bytes(),
+            keccak256("E").as_bytes()
+        ));
+        assert!(!constant_time_eq(root.as_bytes(), &root.as_bytes()[..63]));
+        assert!(constant_time_eq(b"", b""));
+    }
+    #[test]
+    fn test_by_name() {
+        assert_eq!(by_name("keccak256").unwrap()("abc"), keccak256("abc"));
+        assert!(by_name("md5").is_err());
+    }
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        check_roots(
+            &sha256,
+            "3a2d911cf034f2ef19a954e546f717072287920f5643993c972e3f912a898fea",
+        );
+    }
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_double_sha256() {
+        assert_eq!(
+            double_sha256("abc"),
+            "4f8b42c22dd3729b519ba6f68d2da7cc5b2d606d05daed5ad5128cc03e6c6358"
+        );
+        check_roots(
+            &double_sha256,
+            "6cdd48e462583f2dde8a896d8dcc7210f79f330402ff53cb6cb62dc59ba919bd",
+        );
+    }
+    #[cfg(feature = "sha3-256")]
+    #[test]
+    fn test_sha3_256() {
+        assert_eq!(
+            sha3_256("abc"),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+        check_roots(
+            &sha3_256,
+            "dffefcd5f404bda6006a13927e172ad3654b3d67de8ed636d10bcb0f5368f512",
+        );
+    }
+    #[cfg(feature = "blake2b")]
+    #[test]
+    fn test_blake2b256() {
+        assert_eq!(
+            blake2b256("abc"),
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+        );
+        check_roots(
+            &blake2b256,
+            "f66a109a5895fb754ae921d66329ab086770f5be5cc76008082f4732d891a9c6",
+        );
+    }
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_blake3() {
+        assert_eq!(
+            blake3("abc"),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        check_roots(
+            &blake3,
+            "c8d62848edfe529c90edf434a8e149a69de329ed3f1021d9e45ec34c6c63f527",
+        );
+    }
+}
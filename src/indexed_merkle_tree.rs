@@ -0,0 +1,324 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::merkle_tree_vec::{depth, verify_proof, MerkleTreeVec};
+
+// Sorted (indexed) Merkle tree, as used by Aztec.
+// The leaves are kept sorted and every leaf commits to its value and to the value of its successor,
+// so the absence of a value is proven by revealing the "low leaf": the leaf whose value is smaller
+// and whose successor is bigger than (or missing after) the queried value.
+// The first leaf is a sentinel with an empty value, so every non-empty value has a low leaf.
+pub struct IndexedMerkleTree<'a> {
+    pub tree: MerkleTreeVec<'a>,
+    pub values: Vec<String>,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LowLeafProof {
+    pub value: String,
+    // Empty if the low leaf is the last one.
+    pub next_value: String,
+    pub index: usize,
+    pub proof: Vec<String>,
+}
+
+// Each leaf is "leaf:", h(value) and h(next_value).
+// Both hashes have a fixed length, so the concatenation is unambiguous, and the prefix keeps a leaf
+// apart from the two hex child hashes hashed into a parent: otherwise two siblings could be
+// passed off as a low leaf one level up.
+pub fn encode_leaf(value: &str, next_value: &str, hash_fn: &dyn Fn(&str) -> String) -> String {
+    format!("leaf:{}{}", hash_fn(value), hash_fn(next_value))
+}
+
+impl<'a> IndexedMerkleTree<'a> {
+    pub fn new(
+        values: &[String],
+        hash_fn: &'a dyn Fn(&str) -> String,
+    ) -> Result<IndexedMerkleTree<'a>, String> {
+        let mut sorted_values = vec![String::new()];
+        sorted_values.extend(values.iter().cloned());
+        sorted_values.sort();
+
+        for pair in sorted_values.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(if pair[0].is_empty() {
+                    "Empty values are reserved".to_string()
+                } else {
+                    "Duplicated value".to_string()
+                });
+            }
+        }
+
+        let leaves = IndexedMerkleTree::encode_leaves(&sorted_values, hash_fn);
+
+        Ok(IndexedMerkleTree {
+            tree: MerkleTreeVec::new(&leaves, hash_fn),
+            values: sorted_values,
+            hash_fn,
+        })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.tree.root
+    }
+
+    // Number of leaves, with the sentinel: the verifiers need it along with the root
+    pub fn leaves_count(&self) -> usize {
+        self.values.len()
+    }
+
+    // The value is inserted at its sorted position, which shifts the leaves after it, so the tree is
+    // rebuilt from every value (O(n) hashes). Aztec instead appends the leaf at the next free index
+    // and only updates the low leaf, but then the leaves are not sorted any more.
+    pub fn insert(&mut self, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return Err("Empty values are reserved".to_string());
+        }
+        match self.values.binary_search_by(|v| v.as_str().cmp(value)) {
+            Ok(_) => Err("Duplicated value".to_string()),
+            Err(position) => {
+                self.values.insert(position, value.to_string());
+                let leaves = IndexedMerkleTree::encode_leaves(&self.values, self.hash_fn);
+                self.tree = MerkleTreeVec::new(&leaves, self.hash_fn);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn get_membership_proof(&self, value: &str) -> Result<LowLeafProof, String> {
+        match self.values.binary_search_by(|v| v.as_str().cmp(value)) {
+            Ok(index) if !value.is_empty() => self.low_leaf_proof(index),
+            _ => Err("Item not found".to_string()),
+        }
+    }
+
+    pub fn get_non_membership_proof(&self, value: &str) -> Result<LowLeafProof, String> {
+        match self.values.binary_search_by(|v| v.as_str().cmp(value)) {
+            Ok(_) => Err("Item found".to_string()),
+            // The sentinel is always the smallest value, so position is never 0
+            Err(position) => self.low_leaf_proof(position - 1),
+        }
+    }
+
+    fn low_leaf_proof(&self, index: usize) -> Result<LowLeafProof, String> {
+        let next_value = self.values.get(index + 1).cloned().unwrap_or_default();
+
+        Ok(LowLeafProof {
+            value: self.values[index].clone(),
+            next_value,
+            index,
+            proof: self.tree.get_proof_by_index(index)?,
+        })
+    }
+
+    fn encode_leaves(sorted_values: &[String], hash_fn: &dyn Fn(&str) -> String) -> Vec<String> {
+        sorted_values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let next = sorted_values.get(i + 1).map(|s| s.as_str()).unwrap_or("");
+                encode_leaf(v, next, hash_fn)
+            })
+            .collect()
+    }
+}
+
+// `leaves_count` is the number of leaves of the tree of `root` (see leaves_count).
+pub fn verify_membership(
+    root: &str,
+    leaves_count: usize,
+    proof: &LowLeafProof,
+    value: &str,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    !value.is_empty() && proof.value == value && verify_low_leaf(root, leaves_count, proof, hash_fn)
+}
+
+// The value is absent if it falls strictly between the low leaf and its successor.
+pub fn verify_non_membership(
+    root: &str,
+    leaves_count: usize,
+    proof: &LowLeafProof,
+    value: &str,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    let bracketed = proof.value.as_str() < value
+        && (proof.next_value.is_empty() || value < proof.next_value.as_str());
+
+    bracketed && verify_low_leaf(root, leaves_count, proof, hash_fn)
+}
+
+// The proof has to go down to the leaves of a tree of that size
+fn verify_low_leaf(
+    root: &str,
+    leaves_count: usize,
+    proof: &LowLeafProof,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    let leaf = encode_leaf(&proof.value, &proof.next_value, hash_fn);
+    (proof.index < leaves_count)
+        & (proof.proof.len() == depth(leaves_count))
+        & verify_proof(root, &proof.proof, &leaf, proof.index, hash_fn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::keccak256;
+    use crate::{slice_to_string, vec_to_string};
+
+    #[test]
+    fn test_new_sorts_and_links() {
+        let itree = IndexedMerkleTree::new(&slice_to_string!["F", "D", "E"], &keccak256).unwrap();
+
+        assert_eq!(itree.values, vec_to_string!["", "D", "E", "F"]);
+        assert_eq!(
+            itree.tree.initial_leaves[1],
+            encode_leaf("D", "E", &keccak256)
+        );
+        assert_eq!(
+            itree.tree.initial_leaves[3],
+            encode_leaf("F", "", &keccak256)
+        );
+    }
+    #[test]
+    fn test_new_rejects_duplicates() {
+        assert!(IndexedMerkleTree::new(&slice_to_string!["D", "D"], &keccak256).is_err());
+        assert!(IndexedMerkleTree::new(&slice_to_string![""], &keccak256).is_err());
+    }
+    #[test]
+    fn test_membership() {
+        let itree = IndexedMerkleTree::new(&slice_to_string!["D", "F", "H"], &keccak256).unwrap();
+
+        let proof = itree.get_membership_proof("F").unwrap();
+        assert!(verify_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "F",
+            &keccak256
+        ));
+        assert!(!verify_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "D",
+            &keccak256
+        ));
+        assert!(itree.get_membership_proof("E").is_err());
+    }
+    #[test]
+    fn test_non_membership() {
+        let itree = IndexedMerkleTree::new(&slice_to_string!["D", "F", "H"], &keccak256).unwrap();
+
+        let proof = itree.get_non_membership_proof("E").unwrap();
+        assert_eq!(proof.value, "D");
+        assert_eq!(proof.next_value, "F");
+        assert!(verify_non_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "E",
+            &keccak256
+        ));
+        // The same low leaf does not bracket a value after its successor
+        assert!(!verify_non_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "G",
+            &keccak256
+        ));
+
+        // Smaller than every value -> the sentinel is the low leaf
+        let proof = itree.get_non_membership_proof("A").unwrap();
+        assert_eq!(proof.value, "");
+        assert!(verify_non_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "A",
+            &keccak256
+        ));
+
+        // Bigger than every value -> the last leaf is the low leaf
+        let proof = itree.get_non_membership_proof("Z").unwrap();
+        assert_eq!(proof.next_value, "");
+        assert!(verify_non_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "Z",
+            &keccak256
+        ));
+
+        assert!(itree.get_non_membership_proof("F").is_err());
+    }
+    #[test]
+    fn test_insert() {
+        let mut itree = IndexedMerkleTree::new(&slice_to_string!["D", "H"], &keccak256).unwrap();
+        let old_root = itree.root().to_string();
+        let old_count = itree.leaves_count();
+
+        let proof = itree.get_non_membership_proof("F").unwrap();
+        itree.insert("F").unwrap();
+
+        assert!(!verify_non_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "F",
+            &keccak256
+        ));
+        assert!(verify_non_membership(
+            &old_root, old_count, &proof, "F", &keccak256
+        ));
+
+        let cmp = IndexedMerkleTree::new(&slice_to_string!["H", "F", "D"], &keccak256).unwrap();
+        assert_eq!(itree.root(), cmp.root());
+
+        let proof = itree.get_membership_proof("F").unwrap();
+        assert!(verify_membership(
+            itree.root(),
+            itree.leaves_count(),
+            &proof,
+            "F",
+            &keccak256
+        ));
+        assert!(itree.insert("F").is_err());
+    }
+    #[test]
+    fn test_forged_low_leaf() {
+        let values = slice_to_string!["A", "B", "C", "D", "F", "H", "K"];
+        let itree = IndexedMerkleTree::new(&values, &keccak256).unwrap();
+        let leaves = &itree.tree.initial_leaves;
+        let (root, count) = (itree.root(), itree.leaves_count());
+
+        // Two siblings passed off as the low leaf of their parent, one level up
+        let forged = LowLeafProof {
+            value: leaves[4].clone(),
+            next_value: leaves[5].clone(),
+            index: 2,
+            proof: itree.tree.get_proof_by_index(4).unwrap()[1..].to_vec(),
+        };
+        for member in ["D", "F", "H"] {
+            assert!(!verify_non_membership(
+                root, count, &forged, member, &keccak256
+            ));
+        }
+
+        // The proof has to reach the leaves of a tree of that size
+        let proof = itree.get_non_membership_proof("E").unwrap();
+        assert!(verify_non_membership(root, count, &proof, "E", &keccak256));
+        assert!(!verify_non_membership(
+            root,
+            2 * count,
+            &proof,
+            "E",
+            &keccak256
+        ));
+    }
+}
@@ -0,0 +1,327 @@
+// k-ary Merkle tree: each parent hashes the concatenation of up to `arity` children.
+// Wider trees are shallower, so a proof has fewer levels, but each level carries all the siblings.
+// With arity 2 and FillPolicy::DuplicateLast the roots and proofs are the ones of MerkleTreeVec.
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashers::constant_time_eq;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FillPolicy {
+    // "Balance" the tree -> Repeat the last node until the group is full
+    #[serde(rename = "duplicate")]
+    DuplicateLast,
+    // Hash the incomplete group with the children it has
+    Truncate,
+}
+
+pub struct KaryMerkleTree<'a> {
+    pub root: String,
+    pub initial_leaves: Vec<String>,
+    pub arity: usize,
+    pub fill: FillPolicy,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KaryProof {
+    pub index: usize,
+    // For each level, the other members of the group in order
+    pub siblings: Vec<Vec<String>>,
+}
+
+impl FillPolicy {
+    // The names of the serialization, as used by the command-line tool
+    pub fn from_name(name: &str) -> Result<FillPolicy, String> {
+        match name {
+            "duplicate" => Ok(FillPolicy::DuplicateLast),
+            "truncate" => Ok(FillPolicy::Truncate),
+            _ => Err(format!("Unknown odd-node policy: {name}")),
+        }
+    }
+}
+
+impl<'a> KaryMerkleTree<'a> {
+    pub fn new(
+        initial_leaves: &[String],
+        arity: usize,
+        fill: FillPolicy,
+        hash_fn: &'a dyn Fn(&str) -> String,
+    ) -> Result<KaryMerkleTree<'a>, String> {
+        if arity < 2 {
+            return Err("Arity must be at least 2".to_string());
+        }
+        if initial_leaves.is_empty() {
+            return Err("Empty tree".to_string());
+        }
+        let mut upper_children = initial_leaves
+            .iter()
+            .map(|s| hash_fn(s))
+            .collect::<Vec<String>>();
+
+        // Even a single leaf goes through one level
+        loop {
+            upper_children = upper_children
+                .chunks(arity)
+                .map(|chunk| hash_fn(&KaryMerkleTree::fill_group(chunk, arity, fill).concat()))
+                .collect();
+            if upper_children.len() == 1 {
+                break;
+            }
+        }
+
+        Ok(KaryMerkleTree {
+            root: upper_children.pop().unwrap(),
+            initial_leaves: initial_leaves.into(),
+            arity,
+            fill,
+            hash_fn,
+        })
+    }
+
+    pub fn get_proof_by_index(&self, index: usize) -> Result<KaryProof, String> {
+        if index >= self.initial_leaves.len() {
+            return Err("Index out of bounds".to_string());
+        }
+        kary_proof_from_levels(&self.levels(), index, self.arity, self.fill)
+    }
+
+    // All the hashes, bottom-up: levels[0] holds the hashed leaves and the last level the root.
+    // This recomputes the tree, keep the result around to build several proofs with
+    // kary_proof_from_levels.
+    pub fn levels(&self) -> Vec<Vec<String>> {
+        let mut levels = vec![self
+            .initial_leaves
+            .iter()
+            .map(|s| (self.hash_fn)(s))
+            .collect::<Vec<String>>()];
+
+        // Even a single leaf goes through one level
+        loop {
+            let upper = levels
+                .last()
+                .unwrap()
+                .chunks(self.arity)
+                .map(|chunk| {
+                    (self.hash_fn)(
+                        &KaryMerkleTree::fill_group(chunk, self.arity, self.fill).concat(),
+                    )
+                })
+                .collect::<Vec<String>>();
+            let is_root = upper.len() == 1;
+            levels.push(upper);
+            if is_root {
+                return levels;
+            }
+        }
+    }
+
+    pub fn get_proof(&self, item: &str) -> Result<KaryProof, String> {
+        let index = self.initial_leaves.iter().position(|x| x == item);
+        match index {
+            Some(x) => self.get_proof_by_index(x),
+            None => Err("Item not found".to_string()),
+        }
+    }
+
+    pub fn verify(&self, proof: &KaryProof, item: &str) -> bool {
+        verify_kary_proof(&self.root, proof, item, self.arity, self.hash_fn)
+    }
+
+    fn fill_group(chunk: &[String], arity: usize, fill: FillPolicy) -> Vec<String> {
+        let mut group = chunk.to_vec();
+        if fill == FillPolicy::DuplicateLast {
+            group.resize(arity, chunk.last().unwrap().clone());
+        }
+        group
+    }
+}
+
+// Proof of the leaf at `index` from the levels of a tree (see KaryMerkleTree::levels), without
+// hashing anything.
+pub fn kary_proof_from_levels(
+    levels: &[Vec<String>],
+    index: usize,
+    arity: usize,
+    fill: FillPolicy,
+) -> Result<KaryProof, String> {
+    if levels.len() < 2 || index >= levels[0].len() {
+        return Err("Index out of bounds".to_string());
+    }
+    let mut siblings = Vec::new();
+    let mut index_item = index;
+
+    for level in &levels[..levels.len() - 1] {
+        let chunk_start = index_item - index_item % arity;
+        let chunk_end = (chunk_start + arity).min(level.len());
+        let mut group = KaryMerkleTree::fill_group(&level[chunk_start..chunk_end], arity, fill);
+        group.remove(index_item % arity);
+        siblings.push(group);
+        index_item /= arity;
+    }
+
+    Ok(KaryProof { index, siblings })
+}
+
+pub fn verify_kary_proof(
+    root: &str,
+    proof: &KaryProof,
+    item: &str,
+    arity: usize,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    // No tree has this arity, and a group of one node would just rehash it
+    if arity < 2 {
+        return false;
+    }
+    let mut valid = true;
+    let mut check_root = hash_fn(item);
+    let mut index = proof.index;
+
+    for siblings in &proof.siblings {
+        let position = index % arity;
+        // A malformed level makes the proof invalid, the walk still goes on
+        valid &= siblings.len() < arity && position <= siblings.len();
+        let mut group = siblings.clone();
+        group.insert(position.min(siblings.len()), check_root);
+        check_root = hash_fn(&group.concat());
+        index /= arity;
+    }
+    valid & (index == 0) & constant_time_eq(check_root.as_bytes(), root.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_binary_same_as_vec() {
+        for size in 1..=9 {
+            let leaves = leaves(size);
+            let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+            let ktree =
+                KaryMerkleTree::new(&leaves, 2, FillPolicy::DuplicateLast, &keccak256).unwrap();
+
+            assert_eq!(ktree.root, mtree.root);
+            for index in 0..size {
+                let proof = ktree.get_proof_by_index(index).unwrap();
+                let flat = proof.siblings.concat();
+                assert_eq!(flat, mtree.get_proof_by_index(index).unwrap());
+            }
+        }
+    }
+    #[test]
+    fn test_new_arity_4() {
+        let d = "D".to_string();
+        let e = "E".to_string();
+        let f = "F".to_string();
+        let g = "G".to_string();
+        let h = "H".to_string();
+
+        let hashes = [&d, &e, &f, &g, &h].map(|s| keccak256(s));
+        let defg_hash = keccak256(&hashes[..4].concat());
+
+        let duplicated = keccak256(&hashes[4].repeat(4));
+        let ktree = KaryMerkleTree::new(
+            &[d.clone(), e.clone(), f.clone(), g.clone(), h.clone()],
+            4,
+            FillPolicy::DuplicateLast,
+            &keccak256,
+        )
+        .unwrap();
+        assert_eq!(
+            ktree.root,
+            keccak256(&format!(
+                "{}{}{}{}",
+                defg_hash, duplicated, duplicated, duplicated
+            ))
+        );
+
+        let truncated = keccak256(&hashes[4]);
+        let ktree =
+            KaryMerkleTree::new(&[d, e, f, g, h], 4, FillPolicy::Truncate, &keccak256).unwrap();
+        assert_eq!(
+            ktree.root,
+            keccak256(&format!("{}{}", defg_hash, truncated))
+        );
+    }
+    #[test]
+    fn test_proofs() {
+        for arity in [2, 3, 4, 8, 16] {
+            for fill in [FillPolicy::DuplicateLast, FillPolicy::Truncate] {
+                let leaves = leaves(37);
+                let ktree = KaryMerkleTree::new(&leaves, arity, fill, &keccak256).unwrap();
+
+                for (index, leaf) in leaves.iter().enumerate() {
+                    let proof = ktree.get_proof_by_index(index).unwrap();
+                    assert!(ktree.verify(&proof, leaf));
+                    assert!(!ktree.verify(&proof, "X"));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_proof_siblings_per_level() {
+        let ktree =
+            KaryMerkleTree::new(&leaves(64), 16, FillPolicy::DuplicateLast, &keccak256).unwrap();
+        let proof = ktree.get_proof("17").unwrap();
+
+        assert_eq!(proof.siblings.len(), 2);
+        assert!(proof.siblings.iter().all(|s| s.len() == 15));
+        assert!(KaryMerkleTree::new(&leaves(4), 1, FillPolicy::Truncate, &keccak256).is_err());
+    }
+    #[test]
+    fn test_proof_from_levels() {
+        for fill in [FillPolicy::DuplicateLast, FillPolicy::Truncate] {
+            let ktree = KaryMerkleTree::new(&leaves(10), 3, fill, &keccak256).unwrap();
+            let levels = ktree.levels();
+            assert_eq!(
+                levels.iter().map(|l| l.len()).collect::<Vec<usize>>(),
+                vec![10, 4, 2, 1]
+            );
+            assert_eq!(levels[3][0], ktree.root);
+
+            for index in 0..10 {
+                let proof = kary_proof_from_levels(&levels, index, 3, fill).unwrap();
+                assert!(ktree.verify(&proof, &index.to_string()));
+            }
+            assert!(kary_proof_from_levels(&levels, 10, 3, fill).is_err());
+        }
+    }
+    #[test]
+    fn test_verify_invalid_arity() {
+        let ktree =
+            KaryMerkleTree::new(&leaves(4), 2, FillPolicy::DuplicateLast, &keccak256).unwrap();
+        let proof = ktree.get_proof_by_index(0).unwrap();
+        assert!(verify_kary_proof(&ktree.root, &proof, "0", 2, &keccak256));
+        assert!(!verify_kary_proof(&ktree.root, &proof, "0", 0, &keccak256));
+
+        // With an arity of 1, levels without siblings would rehash any leaf up to any depth
+        let forged = KaryProof {
+            index: 0,
+            siblings: vec![Vec::new(); 2],
+        };
+        let root = keccak256(&keccak256(&keccak256("X")));
+        assert!(!verify_kary_proof(&root, &forged, "X", 1, &keccak256));
+
+        let mut malformed = proof.clone();
+        malformed.siblings[0].push(keccak256("1"));
+        assert!(!verify_kary_proof(
+            &ktree.root,
+            &malformed,
+            "0",
+            2,
+            &keccak256
+        ));
+    }
+}
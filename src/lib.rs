@@ -0,0 +1,39 @@
+// Without the "std" feature (on by default) the crate is no_std: the trees, the proofs and their
+// verification only need an allocator. Everything that reads files or sockets requires std.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[doc(hidden)]
+pub extern crate alloc;
+
+pub mod binary_tree;
+#[cfg(feature = "std")]
+pub mod bitcoin;
+#[cfg(feature = "std")]
+pub mod cdc;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+pub mod directory;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hashers;
+pub mod indexed_merkle_tree;
+pub mod kary_merkle_tree;
+pub mod keccak;
+#[cfg(feature = "std")]
+pub mod merkle_stream;
+pub mod merkle_tree;
+pub mod merkle_tree_vec;
+#[cfg(feature = "std")]
+pub mod node_store;
+pub mod parallel;
+#[cfg(feature = "std")]
+pub mod piece_tree;
+#[cfg(feature = "poseidon")]
+pub mod poseidon;
+pub mod proof;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod sync;
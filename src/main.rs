@@ -1,26 +1,16 @@
-mod merkle_tree;
-mod merkle_tree_vec;
+use std::io;
+use std::process::ExitCode;
 
-use crate::merkle_tree::MerkleTreeDs;
-use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
-fn main() -> Result<(), String> {
-    let mtree = MerkleTreeDs::new(vec_to_string!["D"]);
-    let mut mtree_vec = MerkleTreeVec::new(&slice_to_string!["D"], &keccak256);
+use merkletree_in_rust::cli;
 
-    let d_proof = mtree_vec.get_proof("D")?;
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
 
-    for (i, x) in d_proof.iter().enumerate() {
-        println!("proof of D[{i}]: {}", x);
+    match cli::run(&args, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
     }
-    let verify = mtree_vec.verify(d_proof, "D");
-
-    println!("Is the proof of D right? {}", verify.unwrap());
-    println!("{mtree}");
-    println!("Root Before push: {}", mtree_vec.root);
-
-    println!("Now another D leaf is pushed, but since the original tree has to duplicate D, the output should be the same");
-    mtree_vec.push_to_initial(&mut vec_to_string!["D"]);
-
-    println!("Root After push: {}", mtree_vec.root);
-    Ok(())
 }
@@ -0,0 +1,125 @@
+use std::io::BufRead;
+
+// Computes the same root as MerkleTreeVec::new without holding the leaves in memory.
+// Only the roots of the pending (complete) subtrees are kept, at most one per level,
+// so the memory needed is O(log n) for n leaves.
+pub struct MerkleStreamBuilder<'a> {
+    // (level, hash) pairs, the levels are strictly decreasing from bottom to top
+    stack: Vec<(usize, String)>,
+    leaves_count: usize,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+impl<'a> MerkleStreamBuilder<'a> {
+    pub fn new(hash_fn: &'a dyn Fn(&str) -> String) -> MerkleStreamBuilder<'a> {
+        MerkleStreamBuilder {
+            stack: Vec::new(),
+            leaves_count: 0,
+            hash_fn,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves_count == 0
+    }
+
+    pub fn push(&mut self, leaf: &str) {
+        let mut node = (0, (self.hash_fn)(leaf));
+
+        // Two subtrees of the same level are siblings -> merge them
+        while let Some((level, _)) = self.stack.last() {
+            if *level != node.0 {
+                break;
+            }
+            let (level, left) = self.stack.pop().unwrap();
+            node = (level + 1, (self.hash_fn)(&format!("{}{}", left, node.1)));
+        }
+
+        self.stack.push(node);
+        self.leaves_count += 1;
+    }
+
+    pub fn push_all<I, S>(&mut self, leaves: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for leaf in leaves {
+            self.push(leaf.as_ref());
+        }
+    }
+
+    // Each line is a leaf, the line endings are not part of the leaf.
+    pub fn push_reader<R: BufRead>(&mut self, reader: R) -> std::io::Result<()> {
+        for line in reader.lines() {
+            self.push(&line?);
+        }
+        Ok(())
+    }
+
+    // The pending subtrees are merged from the right, duplicating the last node of a level
+    // when it has no sibling, which is the same "balance" rule used by MerkleTreeVec::new.
+    pub fn root(&self) -> Result<String, String> {
+        let mut stack = self.stack.clone();
+        let mut node = stack.pop().ok_or("Empty tree".to_string())?;
+
+        // Even a single leaf is paired with itself
+        while !stack.is_empty() || node.0 == 0 {
+            let left = match stack.last() {
+                Some((level, _)) if *level == node.0 => stack.pop().unwrap().1,
+                // "Balance" the tree -> Duplicate if there is no left sibling
+                _ => node.1.clone(),
+            };
+            node = (node.0 + 1, (self.hash_fn)(&format!("{}{}", left, node.1)));
+        }
+
+        Ok(node.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+    use crate::slice_to_string;
+
+    #[test]
+    fn test_root_same_as_vec() {
+        for size in 1..=33 {
+            let leaves = (0..size).map(|i| i.to_string()).collect::<Vec<String>>();
+
+            let mut builder = MerkleStreamBuilder::new(&keccak256);
+            builder.push_all(&leaves);
+
+            let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+            assert_eq!(builder.root().unwrap(), mtree.root, "{size} leaves");
+        }
+    }
+    #[test]
+    fn test_stack_is_logarithmic() {
+        let mut builder = MerkleStreamBuilder::new(&keccak256);
+        for i in 0..1000 {
+            builder.push(&i.to_string());
+            assert!(builder.stack.len() <= 10);
+        }
+        assert_eq!(builder.len(), 1000);
+    }
+    #[test]
+    fn test_push_reader() {
+        let mut builder = MerkleStreamBuilder::new(&keccak256);
+        builder.push_reader("D\nE\nF\n".as_bytes()).unwrap();
+
+        let mtree = MerkleTreeVec::new(&slice_to_string!["D", "E", "F"], &keccak256);
+        assert_eq!(builder.root().unwrap(), mtree.root);
+    }
+    #[test]
+    fn test_empty() {
+        let builder = MerkleStreamBuilder::new(&keccak256);
+        assert!(builder.is_empty());
+        assert!(builder.root().is_err());
+    }
+}
@@ -1,8 +1,11 @@
-extern crate crypto;
-use std::fmt;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
 
-use self::crypto::digest::Digest;
-use crypto::sha3::Sha3;
+use crate::merkle_tree_vec::keccak256;
+use crate::parallel;
 
 #[derive(Clone, Debug)]
 pub struct Node {
@@ -16,6 +19,7 @@ type Leaf = Option<Box<Node>>;
 #[derive(Debug)]
 pub struct MerkleTreeDs {
     pub root: Leaf,
+    pub leaves_count: usize,
 }
 
 // How can i "hide" this enum? -> How to make it more private?
@@ -37,22 +41,43 @@ impl fmt::Display for MerkleTreeDs {
     }
 }
 
+impl FromIterator<String> for MerkleTreeDs {
+    fn from_iter<I: IntoIterator<Item = String>>(leaves: I) -> Self {
+        MerkleTreeDs::new(leaves.into_iter().collect())
+    }
+}
+
+impl Extend<String> for MerkleTreeDs {
+    // Like FromIterator, the new leaves and the nodes are hashed with Keccak-256: trees of
+    // another hash function have to be rebuilt with new_with_hasher.
+    fn extend<I: IntoIterator<Item = String>>(&mut self, leaves: I) {
+        let mut hashed_values = self.iter_leaves().map(|(_, hash)| hash).collect::<Vec<_>>();
+        hashed_values.extend(leaves.into_iter().map(|leaf| keccak256(&leaf)));
+        *self = MerkleTreeDs::from_hashed_leaves(&hashed_values, &keccak256);
+    }
+}
+
 impl MerkleTreeDs {
     pub fn new(initial_leaves: Vec<String>) -> MerkleTreeDs {
-        //todo!("Check Length");
-        let mut upper_children: Vec<Leaf> = Vec::new();
-
-        let hashed_values = initial_leaves
-            .clone()
-            .iter_mut()
-            .map(|x| {
-                let mut sha3 = Sha3::keccak256();
-                sha3.input_str(x);
-                sha3.result_str()
-            })
-            .collect::<Vec<String>>();
+        MerkleTreeDs::new_with_hasher(initial_leaves, &keccak256)
+    }
 
-        for chunk in hashed_values.chunks(2) {
+    // The hash function has to be Sync, with the "parallel" feature it is called across threads.
+    // See the hashers module for ready-made ones.
+    pub fn new_with_hasher(
+        initial_leaves: Vec<String>,
+        hash_fn: &(dyn Fn(&str) -> String + Sync),
+    ) -> MerkleTreeDs {
+        // With the "parallel" feature, big levels are hashed across threads
+        let hashed_values = parallel::map(&initial_leaves, |x| hash_fn(x));
+        MerkleTreeDs::from_hashed_leaves(&hashed_values, hash_fn)
+    }
+
+    fn from_hashed_leaves(
+        hashed_values: &[String],
+        hash_fn: &(dyn Fn(&str) -> String + Sync),
+    ) -> MerkleTreeDs {
+        let mut upper_children = parallel::map_chunks(hashed_values, |chunk| {
             // "Balance" the tree -> Duplicate if there is no right leaf
             let right = if chunk.len() > 1 {
                 chunk[1].clone()
@@ -71,30 +96,17 @@ impl MerkleTreeDs {
                 right_node: None,
             });
 
-            let combined_hash = {
-                let mut sha3 = Sha3::keccak256();
-                sha3.input_str(&format!("{}{}", chunk[0].clone(), right));
-                sha3.result_str()
-            };
+            let combined_hash = hash_fn(&format!("{}{}", chunk[0].clone(), right));
 
-            let upper = Box::new(Node {
+            Some(Box::new(Node {
                 hash: combined_hash,
                 left_node: Some(child_left),
                 right_node: Some(child_right),
-            });
-            upper_children.push(Some(upper));
-        }
-
-        if upper_children.len() == 1 {
-            return MerkleTreeDs {
-                root: upper_children.pop().unwrap(),
-            };
-        }
+            }))
+        });
 
         while upper_children.len() > 1 {
-            let mut new_upper_children: Vec<Leaf> = Vec::new();
-
-            for chunk in upper_children.chunks(2) {
+            upper_children = parallel::map_chunks(&upper_children, |chunk| {
                 let left = chunk[0].clone().unwrap();
                 // "Balance" the tree -> Duplicate if there is no right leaf
                 let right = if chunk.len() > 1 {
@@ -103,25 +115,115 @@ impl MerkleTreeDs {
                     chunk[0].clone().unwrap()
                 };
 
-                let combined_hash = {
-                    let mut sha3 = Sha3::keccak256();
-                    sha3.input_str(&format!("{}{}", left.hash, right.hash));
-                    sha3.result_str()
-                };
+                let combined_hash = hash_fn(&format!("{}{}", left.hash, right.hash));
 
-                let upper = Box::new(Node {
+                Some(Box::new(Node {
                     hash: combined_hash,
-                    left_node: Some(left.clone()),
-                    right_node: Some(right.clone()),
-                });
-                new_upper_children.push(Some(upper));
-            }
-            upper_children = new_upper_children;
+                    left_node: Some(left),
+                    right_node: Some(right),
+                }))
+            });
         }
 
         MerkleTreeDs {
-            root: upper_children.pop().unwrap(),
+            // None without leaves
+            root: upper_children.pop().flatten(),
+            leaves_count: hashed_values.len(),
+        }
+    }
+
+    // Indices of the leaves that differ between two trees of the same size.
+    // Subtrees with equal hashes are skipped, so the cost is O(d * log n) for d differences.
+    pub fn diff(&self, other: &MerkleTreeDs) -> Result<Vec<usize>, String> {
+        if self.leaves_count != other.leaves_count {
+            return Err("Trees of different sizes".to_string());
+        }
+        let mut indices = Vec::new();
+        if let (Some(a), Some(b)) = (&self.root, &other.root) {
+            self.mismatched_leaves(a, b, MerkleTreeDs::height(a), 0, &mut indices);
+        }
+        Ok(indices)
+    }
+
+    fn mismatched_leaves(
+        &self,
+        a: &Node,
+        b: &Node,
+        height: usize,
+        position: usize,
+        indices: &mut Vec<usize>,
+    ) {
+        // Duplicated odd nodes only cover positions past the last leaf
+        if a.hash == b.hash || position << height >= self.leaves_count {
+            return;
+        }
+        match (&a.left_node, &a.right_node, &b.left_node, &b.right_node) {
+            (Some(a_left), Some(a_right), Some(b_left), Some(b_right)) => {
+                self.mismatched_leaves(a_left, b_left, height - 1, 2 * position, indices);
+                self.mismatched_leaves(a_right, b_right, height - 1, 2 * position + 1, indices);
+            }
+            _ => indices.push(position),
+        }
+    }
+
+    fn height(node: &Node) -> usize {
+        match &node.left_node {
+            Some(left) => MerkleTreeDs::height(left) + 1,
+            None => 0,
+        }
+    }
+
+    // All the hashes, bottom-up: levels[0] holds the hashed leaves and the last level the root.
+    // The duplicated odd nodes are left out, like in MerkleTreeVec::levels.
+    pub fn levels(&self) -> Vec<Vec<String>> {
+        let mut levels = Vec::new();
+        if let Some(root) = &self.root {
+            let height = MerkleTreeDs::height(root);
+            levels.resize(height + 1, Vec::new());
+            self.collect_level_nodes(root, height, 0, &mut levels);
         }
+        levels
+    }
+
+    fn collect_level_nodes(
+        &self,
+        node: &Node,
+        height: usize,
+        position: usize,
+        levels: &mut Vec<Vec<String>>,
+    ) {
+        // Duplicated odd nodes only cover positions past the last leaf
+        if position << height >= self.leaves_count {
+            return;
+        }
+        levels[height].push(node.hash.clone());
+        if let (Some(left), Some(right)) = (&node.left_node, &node.right_node) {
+            self.collect_level_nodes(left, height - 1, 2 * position, levels);
+            self.collect_level_nodes(right, height - 1, 2 * position + 1, levels);
+        }
+    }
+
+    pub fn iter_levels(&self) -> impl Iterator<Item = Vec<String>> {
+        self.levels().into_iter()
+    }
+
+    // (index, hash) of every leaf. The original values are not kept by MerkleTreeDs.
+    pub fn iter_leaves(&self) -> impl Iterator<Item = (usize, String)> {
+        self.iter_levels()
+            .next()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+    }
+
+    // (level, index, hash) of every node, bottom-up and left to right
+    pub fn iter_nodes(&self) -> impl Iterator<Item = (usize, usize, String)> {
+        self.iter_levels().enumerate().flat_map(|(level, hashes)| {
+            hashes
+                .into_iter()
+                .enumerate()
+                .map(move |(index, hash)| (level, index, hash))
+        })
     }
 
     fn print_node(
@@ -180,6 +282,11 @@ impl MerkleTreeDs {
 mod tests {
     use super::MerkleTreeDs;
     use super::*;
+    #[cfg(feature = "std")]
+    use crypto::digest::Digest;
+    #[cfg(feature = "std")]
+    use crypto::sha3::Sha3;
+    #[cfg(feature = "std")]
     #[test]
     fn test_new_no_duplication() {
         let d = "D".to_string();
@@ -203,6 +310,7 @@ mod tests {
 
         assert_eq!(mtree.root.unwrap().hash, sha3.result_str());
     }
+    #[cfg(feature = "std")]
     #[test]
     fn test_new_with_duplication() {
         let d = "D".to_string();
@@ -220,4 +328,86 @@ mod tests {
 
         assert_eq!(mtree.root.unwrap().hash, sha3.result_str());
     }
+    #[test]
+    fn test_new_same_root_as_vec() {
+        use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+        use crate::parallel::PARALLEL_THRESHOLD;
+
+        // Crosses the parallel threshold when the "parallel" feature is enabled
+        let leaves = (0..2 * PARALLEL_THRESHOLD + 3)
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>();
+
+        let mtree = MerkleTreeDs::new(leaves.clone());
+        let mtree_vec = MerkleTreeVec::new(&leaves, &keccak256);
+
+        assert_eq!(mtree.root.unwrap().hash, mtree_vec.root);
+    }
+    #[test]
+    fn test_diff() {
+        let leaves = (0..11).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mtree = MerkleTreeDs::new(leaves.clone());
+
+        let mut changed = leaves.clone();
+        changed[3] = "changed".to_string();
+        changed[10] = "changed".to_string();
+        let other = MerkleTreeDs::new(changed);
+
+        assert_eq!(mtree.diff(&other).unwrap(), vec![3, 10]);
+        assert!(mtree.diff(&mtree).unwrap().is_empty());
+        assert!(mtree
+            .diff(&MerkleTreeDs::new(leaves[1..].to_vec()))
+            .is_err());
+    }
+    #[test]
+    fn test_iterators() {
+        let leaves = ["D", "E", "F", "G", "H"].map(|s| s.to_string());
+        let mtree = leaves.iter().cloned().collect::<MerkleTreeDs>();
+        let mtree_vec = crate::merkle_tree_vec::MerkleTreeVec::new(&leaves, &keccak256);
+
+        assert_eq!(mtree.levels(), mtree_vec.levels());
+        assert_eq!(
+            mtree.iter_leaves().collect::<Vec<(usize, String)>>(),
+            vec![
+                (0, keccak256("D")),
+                (1, keccak256("E")),
+                (2, keccak256("F")),
+                (3, keccak256("G")),
+                (4, keccak256("H")),
+            ]
+        );
+        // 5 leaves, then 3, 2 and the root
+        let nodes = mtree.iter_nodes().collect::<Vec<(usize, usize, String)>>();
+        assert_eq!(nodes.len(), 5 + 3 + 2 + 1);
+        assert_eq!(
+            nodes.last().unwrap(),
+            &(3, 0, mtree.root.as_ref().unwrap().hash.clone())
+        );
+        assert!(nodes
+            .iter()
+            .eq(mtree_vec.iter_nodes().collect::<Vec<_>>().iter()));
+    }
+    #[test]
+    fn test_extend() {
+        let leaves = ["D", "E", "F", "G", "H"].map(|s| s.to_string());
+        let mut mtree = leaves[..2].iter().cloned().collect::<MerkleTreeDs>();
+        mtree.extend(leaves[2..].iter().cloned());
+
+        let cmp = leaves.iter().cloned().collect::<MerkleTreeDs>();
+        assert_eq!(mtree.leaves_count, 5);
+        assert_eq!(mtree.levels(), cmp.levels());
+
+        let mut empty = core::iter::empty::<String>().collect::<MerkleTreeDs>();
+        empty.extend(leaves.iter().cloned());
+        assert_eq!(empty.levels(), cmp.levels());
+    }
+    #[test]
+    fn test_empty() {
+        let mtree = core::iter::empty::<String>().collect::<MerkleTreeDs>();
+        assert!(mtree.root.is_none());
+        assert_eq!(mtree.leaves_count, 0);
+        assert!(mtree.levels().is_empty());
+        assert_eq!(mtree.iter_nodes().count(), 0);
+        assert_eq!(mtree.to_string(), "Empty Merkle Tree");
+    }
 }
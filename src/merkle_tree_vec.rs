@@ -1,38 +1,63 @@
+#[cfg(feature = "std")]
 extern crate crypto;
 
+#[cfg(feature = "std")]
 use self::crypto::digest::Digest;
+#[cfg(feature = "std")]
 use crypto::sha3::Sha3;
 
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use crate::keccak;
+
+use crate::hashers::constant_time_eq;
+#[cfg(feature = "parallel")]
+use crate::parallel;
+
 pub struct MerkleTreeVec<'a> {
     pub root: String,
     pub initial_leaves: Vec<String>,
     hash_fn: &'a dyn Fn(&str) -> String,
 }
 
+#[cfg(feature = "std")]
 pub fn keccak256(s: &str) -> String {
     let mut sha3 = Sha3::keccak256();
     sha3.input_str(s);
     sha3.result_str()
 }
 
+#[cfg(not(feature = "std"))]
+pub fn keccak256(s: &str) -> String {
+    keccak::to_hex(&keccak::digest(s.as_bytes()))
+}
+
+// Spelled out with $crate::alloc so they also work in no_std crates
 #[macro_export]
 macro_rules! slice_to_string {
-    ($($x:expr),*) => ([$($x.to_string()),*]);
+    ($($x:expr),*) => ([$($crate::alloc::string::ToString::to_string(&$x)),*]);
 }
 
 #[macro_export]
 macro_rules! vec_to_string {
-    ($($x:expr),*) => (vec![$($x.to_string()),*]);
+    ($($x:expr),*) => ($crate::alloc::vec![$($crate::alloc::string::ToString::to_string(&$x)),*]);
 }
 
 // This code assumes that the length of initial_leaves is a power of 2 (i.e., initial_leaves.len() == 2^N).
 // This condition must be satisfied for optimal performance.
 // Otherwise, additional copy operations may be required at each level of the computation as needed.
 impl<'a> MerkleTreeVec<'a> {
+    /// # Panics
+    /// If `initial_leaves` is empty: the root of a MerkleTreeVec always exists.
     pub fn new(
         initial_leaves: &[String],
         hash_fn: &'a dyn Fn(&str) -> String,
     ) -> MerkleTreeVec<'a> {
+        assert!(!initial_leaves.is_empty(), "Empty tree");
         let mut upper_children: Vec<String> = Vec::new();
 
         let hashed_initial_leaves = initial_leaves
@@ -88,6 +113,35 @@ impl<'a> MerkleTreeVec<'a> {
         }
     }
 
+    // Same root as new(), but the leaves and every level are hashed across threads
+    // once they reach parallel::PARALLEL_THRESHOLD nodes. The hash function has to be Sync.
+    // Panics without leaves, like new().
+    #[cfg(feature = "parallel")]
+    pub fn new_parallel(
+        initial_leaves: &[String],
+        hash_fn: &'a (dyn Fn(&str) -> String + Sync),
+    ) -> MerkleTreeVec<'a> {
+        assert!(!initial_leaves.is_empty(), "Empty tree");
+        let hash_chunk = |chunk: &[String]| {
+            // "Balance" the tree -> Duplicate if there is no right leaf
+            let right = chunk.last().unwrap();
+            hash_fn(&format!("{}{}", chunk[0], right))
+        };
+
+        let hashed_initial_leaves = parallel::map(initial_leaves, |s| hash_fn(s));
+        let mut upper_children = parallel::map_chunks(&hashed_initial_leaves, hash_chunk);
+
+        while upper_children.len() > 1 {
+            upper_children = parallel::map_chunks(&upper_children, hash_chunk);
+        }
+
+        MerkleTreeVec {
+            root: upper_children.pop().unwrap(),
+            initial_leaves: initial_leaves.into(),
+            hash_fn,
+        }
+    }
+
     pub fn push_to_initial(&mut self, new_leaves: &mut Vec<String>) {
         let mut new_initial_leaves = self.initial_leaves.clone();
         new_initial_leaves.append(new_leaves);
@@ -96,9 +150,16 @@ impl<'a> MerkleTreeVec<'a> {
     }
 
     pub fn get_proof(&self, item: &str) -> Result<Vec<String>, String> {
+        self.get_proof_by_index(self.get_index(item)?)
+    }
+
+    pub fn get_proof_by_index(&self, index: usize) -> Result<Vec<String>, String> {
+        if index >= self.initial_leaves.len() {
+            return Err("Index out of bounds".to_string());
+        }
         let mut upper_children: Vec<String> = Vec::new();
         let mut proof: Vec<String> = Vec::new();
-        let mut index_item = self.get_index(item)?;
+        let mut index_item = index;
 
         let hashed_initial_leaves = self
             .initial_leaves
@@ -181,18 +242,90 @@ impl<'a> MerkleTreeVec<'a> {
 
     pub fn verify(&self, proof: Vec<String>, item: &str) -> Result<bool, String> {
         let index = self.get_index(item)?;
-        let mut check_root = keccak256(item);
-        let mut combined_hash;
-        for h in &proof {
-            if index % 2 == 0 {
-                combined_hash = (self.hash_fn)(&format!("{}{}", check_root, h));
-            } else {
-                combined_hash = (self.hash_fn)(&format!("{}{}", h, check_root));
-            }
+        Ok(verify_proof(&self.root, &proof, item, index, self.hash_fn))
+    }
+
+    // All the hashes, bottom-up: levels[0] holds the hashed leaves and the last level the root.
+    // This recomputes the tree, keep the result around to compare against other trees with diff_levels.
+    pub fn levels(&self) -> Vec<Vec<String>> {
+        self.iter_levels().collect()
+    }
 
-            check_root = combined_hash;
+    // Same as levels(), each level is only hashed when it is reached
+    pub fn iter_levels(&self) -> Levels<'_> {
+        Levels {
+            level: Some(
+                self.initial_leaves
+                    .iter()
+                    .map(|s| (self.hash_fn)(s))
+                    .collect(),
+            ),
+            first: true,
+            hash_fn: self.hash_fn,
         }
-        Ok(check_root == self.root)
+    }
+
+    // (index, value, hash) of every leaf
+    pub fn iter_leaves(&self) -> impl Iterator<Item = (usize, &str, String)> {
+        self.initial_leaves
+            .iter()
+            .enumerate()
+            .map(|(index, leaf)| (index, leaf.as_str(), (self.hash_fn)(leaf)))
+    }
+
+    // (level, index, hash) of every node, bottom-up and left to right.
+    // The duplicated odd nodes are left out.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = (usize, usize, String)> + '_ {
+        self.iter_levels().enumerate().flat_map(|(level, hashes)| {
+            hashes
+                .into_iter()
+                .enumerate()
+                .map(move |(index, hash)| (level, index, hash))
+        })
+    }
+
+    // Root the tree had when it only held its first `count` leaves.
+    pub fn root_of_first(&self, count: usize) -> Result<String, String> {
+        if count == 0 || count > self.initial_leaves.len() {
+            return Err("Index out of bounds".to_string());
+        }
+        Ok(MerkleTreeVec::new(&self.initial_leaves[..count], self.hash_fn).root)
+    }
+
+    // Proves that the tree of the first `from` leaves is a prefix of this one: the hash of leaf
+    // `from - 1` followed by its proof. Its left siblings are shared by both trees, and in the
+    // smaller tree it is the last node of every level, so its right siblings are duplicates.
+    pub fn get_consistency_proof(&self, from: usize) -> Result<Vec<String>, String> {
+        if from == 0 {
+            return Err("Index out of bounds".to_string());
+        }
+        let mut siblings = self.get_proof_by_index(from - 1)?;
+        let mut proof = vec![(self.hash_fn)(&self.initial_leaves[from - 1])];
+        proof.append(&mut siblings);
+        Ok(proof)
+    }
+
+    // One proof for the leaves start..end: level by level, only the siblings outside the range
+    // on its left and right boundaries, left first. Duplicated odd nodes are left out.
+    pub fn get_range_proof(&self, start: usize, end: usize) -> Result<Vec<String>, String> {
+        if start >= end || end > self.initial_leaves.len() {
+            return Err("Index out of bounds".to_string());
+        }
+        let levels = self.levels();
+        let mut proof = Vec::new();
+        let (mut lo, mut hi) = (start, end);
+
+        for level in &levels[..levels.len() - 1] {
+            if !lo.is_multiple_of(2) {
+                proof.push(level[lo - 1].clone());
+            }
+            if !hi.is_multiple_of(2) && hi < level.len() {
+                proof.push(level[hi].clone());
+            }
+            lo /= 2;
+            hi = hi.div_ceil(2);
+        }
+        Ok(proof)
     }
 
     pub fn get_index(&self, item: &str) -> Result<usize, String> {
@@ -204,6 +337,209 @@ impl<'a> MerkleTreeVec<'a> {
     }
 }
 
+pub struct Levels<'a> {
+    // Next level to return
+    level: Option<Vec<String>>,
+    first: bool,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+impl Iterator for Levels<'_> {
+    type Item = Vec<String>;
+
+    fn next(&mut self) -> Option<Vec<String>> {
+        let level = self.level.take()?;
+        // Even a single leaf is paired with itself
+        if self.first || level.len() > 1 {
+            self.level = Some(
+                level
+                    .chunks(2)
+                    .map(|chunk| {
+                        // "Balance" the tree -> Duplicate if there is no right leaf
+                        let right = chunk.last().unwrap();
+                        (self.hash_fn)(&format!("{}{}", chunk[0], right))
+                    })
+                    .collect(),
+            );
+        }
+        self.first = false;
+        Some(level)
+    }
+}
+
+impl FromIterator<String> for MerkleTreeVec<'static> {
+    /// Hashed with Keccak-256, use MerkleTreeVec::new for other hash functions.
+    ///
+    /// # Panics
+    /// If there are no leaves, like MerkleTreeVec::new.
+    fn from_iter<I: IntoIterator<Item = String>>(leaves: I) -> Self {
+        MerkleTreeVec::new(&leaves.into_iter().collect::<Vec<String>>(), &keccak256)
+    }
+}
+
+impl Extend<String> for MerkleTreeVec<'_> {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, leaves: I) {
+        self.push_to_initial(&mut leaves.into_iter().collect());
+    }
+}
+
+// Indices of the leaves that differ between the levels of two trees of the same size
+// (see MerkleTreeVec::levels), walking down from the root and skipping equal subtrees.
+pub fn diff_levels(a: &[Vec<String>], b: &[Vec<String>]) -> Result<Vec<usize>, String> {
+    if a.is_empty() || a.len() != b.len() || a[0].len() != b[0].len() {
+        return Err("Trees of different sizes".to_string());
+    }
+    let mut indices = Vec::new();
+    let mut pending = vec![(a.len() - 1, 0)];
+
+    while let Some((level, index)) = pending.pop() {
+        if a[level][index] == b[level][index] {
+            continue;
+        }
+        if level == 0 {
+            indices.push(index);
+            continue;
+        }
+        // Pushed right first, so the indices come out sorted
+        if 2 * index + 1 < a[level - 1].len() {
+            pending.push((level - 1, 2 * index + 1));
+        }
+        pending.push((level - 1, 2 * index));
+    }
+    Ok(indices)
+}
+
+// Rebuilds the root from the item and its proof, without needing the tree.
+// The index selects, level by level, whether the running hash is the left or the right child,
+// and has to fit in the levels of the proof: otherwise one proof would be valid for many indices.
+pub fn verify_proof(
+    root: &str,
+    proof: &[String],
+    item: &str,
+    index: usize,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    let index_fits = proof.len() >= usize::BITS as usize || index >> proof.len() == 0;
+    let mut check_root = hash_fn(item);
+    let mut index = index;
+    for h in proof {
+        check_root = if index.is_multiple_of(2) {
+            hash_fn(&format!("{}{}", check_root, h))
+        } else {
+            hash_fn(&format!("{}{}", h, check_root))
+        };
+        index /= 2;
+    }
+    index_fits & constant_time_eq(check_root.as_bytes(), root.as_bytes())
+}
+
+// Checks a proof from get_consistency_proof: the same leaf hash and left siblings have to
+// rebuild both the old root (with `from` leaves) and the new one (with `to` leaves).
+pub fn verify_consistency(
+    old_root: &str,
+    new_root: &str,
+    from: usize,
+    to: usize,
+    proof: &[String],
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    if from == 0 || from > to || proof.len() != depth(to) + 1 {
+        return false;
+    }
+    let (leaf_hash, siblings) = proof.split_first().unwrap();
+
+    let mut check_root = leaf_hash.clone();
+    let mut index = from - 1;
+    for h in &siblings[..depth(from)] {
+        check_root = if index.is_multiple_of(2) {
+            // Last node of the level in the old tree
+            hash_fn(&format!("{}{}", check_root, check_root))
+        } else {
+            hash_fn(&format!("{}{}", h, check_root))
+        };
+        index /= 2;
+    }
+    // Both roots are always checked, a failure doesn't tell which one was wrong
+    let old_valid = constant_time_eq(check_root.as_bytes(), old_root.as_bytes());
+
+    let mut check_root = leaf_hash.clone();
+    let mut index = from - 1;
+    for h in siblings {
+        check_root = if index.is_multiple_of(2) {
+            hash_fn(&format!("{}{}", check_root, h))
+        } else {
+            hash_fn(&format!("{}{}", h, check_root))
+        };
+        index /= 2;
+    }
+    old_valid & constant_time_eq(check_root.as_bytes(), new_root.as_bytes())
+}
+
+// Rebuilds the root from the leaves start..start + items.len() of a tree of leaves_count leaves
+// and a proof from get_range_proof. Every sibling of the proof has to be used.
+pub fn verify_range_proof(
+    root: &str,
+    proof: &[String],
+    items: &[String],
+    start: usize,
+    leaves_count: usize,
+    hash_fn: &dyn Fn(&str) -> String,
+) -> bool {
+    // Only depends on the arguments, not on the proof
+    if items.is_empty() || start + items.len() > leaves_count {
+        return false;
+    }
+    // A missing sibling doesn't stop the walk, the proof is just invalid
+    let mut valid = true;
+    let mut sibling = |siblings: &mut core::slice::Iter<String>| match siblings.next() {
+        Some(h) => h.clone(),
+        None => {
+            valid = false;
+            String::new()
+        }
+    };
+    let mut siblings = proof.iter();
+    let mut nodes = items.iter().map(|s| hash_fn(s)).collect::<Vec<String>>();
+    let (mut lo, mut hi) = (start, start + items.len());
+    let mut level_len = leaves_count;
+
+    for _ in 0..depth(leaves_count) {
+        if !lo.is_multiple_of(2) {
+            nodes.insert(0, sibling(&mut siblings));
+        }
+        if !hi.is_multiple_of(2) {
+            if hi < level_len {
+                nodes.push(sibling(&mut siblings));
+            } else {
+                // "Balance" the tree -> Duplicate if there is no right leaf
+                nodes.push(nodes.last().unwrap().clone());
+            }
+        }
+        nodes = nodes
+            .chunks(2)
+            .map(|chunk| hash_fn(&format!("{}{}", chunk[0], chunk[1])))
+            .collect();
+        lo /= 2;
+        hi = hi.div_ceil(2);
+        level_len = level_len.div_ceil(2);
+    }
+    valid
+        & siblings.next().is_none()
+        & (nodes.len() == 1)
+        & constant_time_eq(nodes[0].as_bytes(), root.as_bytes())
+}
+
+// Number of levels above the leaves, a single leaf still has one
+pub(crate) fn depth(leaves_count: usize) -> usize {
+    let mut count = leaves_count;
+    let mut depth = 0;
+    while depth == 0 || count > 1 {
+        count = count.div_ceil(2);
+        depth += 1;
+    }
+    depth
+}
+
 #[cfg(test)]
 mod tests {
     use std::vec;
@@ -271,6 +607,79 @@ mod tests {
         assert!(mtree.verify(proof, "D").unwrap());
     }
     #[test]
+    fn test_verify_proof_by_index() {
+        let leaves = vec_to_string!["D", "E", "F", "G", "H"];
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = mtree.get_proof_by_index(index).unwrap();
+            assert!(verify_proof(&mtree.root, &proof, leaf, index, &keccak256));
+            assert!(!verify_proof(&mtree.root, &proof, "X", index, &keccak256));
+        }
+        assert!(mtree.get_proof_by_index(leaves.len()).is_err());
+
+        // Same bits at the levels of the proof, but out of the tree
+        let proof = mtree.get_proof_by_index(2).unwrap();
+        assert_eq!(proof.len(), 3);
+        assert!(!verify_proof(&mtree.root, &proof, "F", 2 + 8, &keccak256));
+        assert!(!verify_proof(
+            &mtree.root,
+            &proof,
+            "F",
+            usize::MAX,
+            &keccak256
+        ));
+    }
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_new_parallel_same_root() {
+        use crate::parallel::PARALLEL_THRESHOLD;
+
+        for size in [
+            1,
+            5,
+            PARALLEL_THRESHOLD - 1,
+            PARALLEL_THRESHOLD,
+            3 * PARALLEL_THRESHOLD + 1,
+        ] {
+            let leaves = (0..size).map(|i| i.to_string()).collect::<Vec<String>>();
+
+            let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+            let mtree_parallel = MerkleTreeVec::new_parallel(&leaves, &keccak256);
+
+            assert_eq!(mtree.root, mtree_parallel.root);
+        }
+    }
+    #[test]
+    fn test_levels() {
+        let leaves = vec_to_string!["D", "E", "F"];
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+        let levels = mtree.levels();
+
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[0][2], keccak256("F"));
+        assert_eq!(levels[2], vec![mtree.root.clone()]);
+
+        let single = MerkleTreeVec::new(&slice_to_string!["D"], &keccak256);
+        assert_eq!(single.levels().last().unwrap(), &vec![single.root.clone()]);
+    }
+    #[test]
+    fn test_diff_levels() {
+        let leaves = (0..9).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mut changed = leaves.clone();
+        changed[0] = "changed".to_string();
+        changed[8] = "changed".to_string();
+
+        let levels = MerkleTreeVec::new(&leaves, &keccak256).levels();
+        let changed_levels = MerkleTreeVec::new(&changed, &keccak256).levels();
+
+        assert_eq!(diff_levels(&levels, &changed_levels).unwrap(), vec![0, 8]);
+        assert!(diff_levels(&levels, &levels).unwrap().is_empty());
+
+        let smaller = MerkleTreeVec::new(&leaves[1..], &keccak256).levels();
+        assert!(diff_levels(&levels, &smaller).is_err());
+    }
+    #[test]
     fn test_get_proof_of4() {
         let d = "D".to_string();
         let e = "E".to_string();
@@ -304,7 +713,6 @@ mod tests {
         let e_hash = keccak256(&e);
         let f_hash = keccak256(&f);
         let g_hash = keccak256(&g);
-        let h_hash = keccak256(&h);
         let i_hash = keccak256(&i);
         let j_hash = keccak256(&j);
         let k_hash = keccak256(&k);
@@ -347,4 +755,160 @@ mod tests {
         let proof = mtree.get_proof(&i);
         assert_eq!(proof.unwrap(), proof_verify);
     }
+    #[test]
+    fn test_consistency_proof() {
+        let leaves = (0..13).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        for from in 1..=13 {
+            let old_root = mtree.root_of_first(from).unwrap();
+            let proof = mtree.get_consistency_proof(from).unwrap();
+            assert!(verify_consistency(
+                &old_root,
+                &mtree.root,
+                from,
+                13,
+                &proof,
+                &keccak256
+            ));
+            if from < 13 {
+                assert!(!verify_consistency(
+                    &mtree.root,
+                    &mtree.root,
+                    from,
+                    13,
+                    &proof,
+                    &keccak256
+                ));
+            }
+        }
+
+        // A tree that rewrote one of the first leaves is not consistent
+        let mut rewritten = leaves.clone();
+        rewritten[0] = "X".to_string();
+        let other = MerkleTreeVec::new(&rewritten, &keccak256);
+        let proof = other.get_consistency_proof(5).unwrap();
+        let old_root = mtree.root_of_first(5).unwrap();
+        assert!(!verify_consistency(
+            &old_root,
+            &other.root,
+            5,
+            13,
+            &proof,
+            &keccak256
+        ));
+        assert!(mtree.get_consistency_proof(0).is_err());
+        assert!(mtree.get_consistency_proof(14).is_err());
+    }
+    #[test]
+    fn test_range_proof() {
+        for size in 1..=13 {
+            let leaves = (0..size).map(|i| i.to_string()).collect::<Vec<String>>();
+            let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+            for start in 0..size {
+                for end in start + 1..=size {
+                    let proof = mtree.get_range_proof(start, end).unwrap();
+                    let items = &leaves[start..end];
+                    assert!(verify_range_proof(
+                        &mtree.root,
+                        &proof,
+                        items,
+                        start,
+                        size,
+                        &keccak256
+                    ));
+                    if start > 0 {
+                        assert!(!verify_range_proof(
+                            &mtree.root,
+                            &proof,
+                            items,
+                            start - 1,
+                            size,
+                            &keccak256
+                        ));
+                    }
+                    if let Some((_, shorter)) = proof.split_last() {
+                        assert!(!verify_range_proof(
+                            &mtree.root,
+                            shorter,
+                            items,
+                            start,
+                            size,
+                            &keccak256
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_range_proof_boundaries_only() {
+        let leaves = (0..16).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        // [3, 9): leaf 2 and leaf 9, then the node of 0..2 and the one of 10..12, then 12..16
+        let proof = mtree.get_range_proof(3, 9).unwrap();
+        assert_eq!(proof.len(), 5);
+
+        let mut items = leaves[3..9].to_vec();
+        assert!(verify_range_proof(
+            &mtree.root,
+            &proof,
+            &items,
+            3,
+            16,
+            &keccak256
+        ));
+        items[2] = "X".to_string();
+        assert!(!verify_range_proof(
+            &mtree.root,
+            &proof,
+            &items,
+            3,
+            16,
+            &keccak256
+        ));
+        // A leaf missing at the end of the range
+        assert!(!verify_range_proof(
+            &mtree.root,
+            &proof,
+            &leaves[3..8],
+            3,
+            16,
+            &keccak256
+        ));
+
+        assert!(mtree.get_range_proof(4, 4).is_err());
+        assert!(mtree.get_range_proof(0, 17).is_err());
+    }
+    #[test]
+    fn test_iterators() {
+        let leaves = slice_to_string!["D", "E", "F"];
+        let mut mtree = leaves.iter().cloned().collect::<MerkleTreeVec>();
+        assert_eq!(mtree.root, MerkleTreeVec::new(&leaves, &keccak256).root);
+
+        let first = mtree.iter_leaves().next().unwrap();
+        assert_eq!(first, (0, "D", keccak256("D")));
+        assert_eq!(mtree.iter_levels().count(), 3);
+        assert_eq!(
+            mtree
+                .iter_nodes()
+                .map(|(level, _, _)| level)
+                .collect::<Vec<usize>>(),
+            vec![0, 0, 0, 1, 1, 2]
+        );
+
+        mtree.extend(vec_to_string!["G", "H"]);
+        assert_eq!(
+            mtree.root,
+            MerkleTreeVec::new(&slice_to_string!["D", "E", "F", "G", "H"], &keccak256).root
+        );
+        assert_eq!(mtree.iter_leaves().count(), 5);
+    }
+    #[test]
+    #[should_panic(expected = "Empty tree")]
+    fn test_collect_empty() {
+        let _ = core::iter::empty::<String>().collect::<MerkleTreeVec>();
+    }
 }
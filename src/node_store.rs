@@ -0,0 +1,394 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// Storage of the node hashes of a tree, addressed by (level, index).
+// Level 0 holds the hashed leaves and the last level holds the root.
+pub trait NodeStore {
+    fn get(&self, level: usize, index: usize) -> Result<Option<String>, String>;
+
+    fn put(&mut self, level: usize, index: usize, hash: String) -> Result<(), String>;
+
+    // Number of nodes stored at the level
+    fn len(&self, level: usize) -> Result<usize, String>;
+
+    // Stores can override this to write all the nodes at once.
+    fn put_batch(&mut self, nodes: Vec<(usize, usize, String)>) -> Result<(), String> {
+        for (level, index, hash) in nodes {
+            self.put(level, index, hash)?;
+        }
+        Ok(())
+    }
+}
+
+// Keeps every level on the heap, like MerkleTreeVec and MerkleTreeDs do.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    levels: Vec<Vec<String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl NodeStore for MemoryStore {
+    fn get(&self, level: usize, index: usize) -> Result<Option<String>, String> {
+        Ok(self.levels.get(level).and_then(|l| l.get(index)).cloned())
+    }
+
+    fn put(&mut self, level: usize, index: usize, hash: String) -> Result<(), String> {
+        if self.levels.len() <= level {
+            self.levels.resize(level + 1, Vec::new());
+        }
+        let nodes = &mut self.levels[level];
+        if nodes.len() <= index {
+            nodes.resize(index + 1, String::new());
+        }
+        nodes[index] = hash;
+        Ok(())
+    }
+
+    fn len(&self, level: usize) -> Result<usize, String> {
+        Ok(self.levels.get(level).map_or(0, |l| l.len()))
+    }
+}
+
+// Keeps each level in its own file inside a directory, so the tree survives restarts.
+// All the hashes have the same length, so every node is a fixed-size record
+// (the hash followed by a newline) and can be read or written with a single seek.
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    hash_len: Option<usize>,
+}
+
+impl FileStore {
+    pub fn open(dir: impl AsRef<Path>) -> Result<FileStore, String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let hash_len = match fs::read_to_string(dir.join("hash_len")) {
+            Ok(s) => Some(s.trim().parse::<usize>().map_err(|e| e.to_string())?),
+            Err(_) => None,
+        };
+
+        Ok(FileStore { dir, hash_len })
+    }
+
+    fn level_path(&self, level: usize) -> PathBuf {
+        self.dir.join(format!("level_{level}"))
+    }
+
+    fn record_len(&mut self, hash: &str) -> Result<usize, String> {
+        match self.hash_len {
+            Some(len) if len != hash.len() => Err("Hash length mismatch".to_string()),
+            Some(len) => Ok(len + 1),
+            None => {
+                fs::write(self.dir.join("hash_len"), hash.len().to_string())
+                    .map_err(|e| e.to_string())?;
+                self.hash_len = Some(hash.len());
+                Ok(hash.len() + 1)
+            }
+        }
+    }
+
+    fn write_records(
+        &mut self,
+        level: usize,
+        mut nodes: Vec<(usize, String)>,
+    ) -> Result<(), String> {
+        if nodes.is_empty() {
+            return Ok(());
+        }
+        let record_len = self.record_len(&nodes[0].1)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.level_path(level))
+            .map_err(|e| e.to_string())?;
+
+        nodes.sort_by_key(|(index, _)| *index);
+        for (index, hash) in nodes {
+            if hash.len() + 1 != record_len {
+                return Err("Hash length mismatch".to_string());
+            }
+            file.seek(SeekFrom::Start((index * record_len) as u64))
+                .map_err(|e| e.to_string())?;
+            file.write_all(format!("{hash}\n").as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        file.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl NodeStore for FileStore {
+    fn get(&self, level: usize, index: usize) -> Result<Option<String>, String> {
+        if index >= self.len(level)? {
+            return Ok(None);
+        }
+        let hash_len = self.hash_len.unwrap_or_default();
+        let mut file = File::open(self.level_path(level)).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start((index * (hash_len + 1)) as u64))
+            .map_err(|e| e.to_string())?;
+
+        let mut buf = vec![0; hash_len];
+        file.read_exact(&mut buf).map_err(|e| e.to_string())?;
+        String::from_utf8(buf).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn put(&mut self, level: usize, index: usize, hash: String) -> Result<(), String> {
+        self.write_records(level, vec![(index, hash)])
+    }
+
+    fn len(&self, level: usize) -> Result<usize, String> {
+        let hash_len = match self.hash_len {
+            Some(len) => len,
+            None => return Ok(0),
+        };
+        match fs::metadata(self.level_path(level)) {
+            Ok(metadata) => Ok(metadata.len() as usize / (hash_len + 1)),
+            Err(_) => Ok(0),
+        }
+    }
+
+    // One open file per level instead of one per node
+    fn put_batch(&mut self, nodes: Vec<(usize, usize, String)>) -> Result<(), String> {
+        let mut levels: Vec<Vec<(usize, String)>> = Vec::new();
+        for (level, index, hash) in nodes {
+            if levels.len() <= level {
+                levels.resize(level + 1, Vec::new());
+            }
+            levels[level].push((index, hash));
+        }
+        for (level, nodes) in levels.into_iter().enumerate() {
+            self.write_records(level, nodes)?;
+        }
+        Ok(())
+    }
+}
+
+// The MerkleTreeVec logic (same roots and proofs) running over any NodeStore.
+// Unlike MerkleTreeVec, every level is stored, so a proof is read from the store instead of
+// recomputing the tree, and a persistent store can be reopened without the leaves.
+pub struct StoredMerkleTree<'a, S: NodeStore> {
+    pub store: S,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+impl<'a, S: NodeStore> StoredMerkleTree<'a, S> {
+    // The store has to be empty: the nodes of a bigger tree would be left after the new ones,
+    // and the sizes and the root read back would be the old ones. Use open() for a stored tree.
+    pub fn build(
+        mut store: S,
+        initial_leaves: &[String],
+        hash_fn: &'a dyn Fn(&str) -> String,
+    ) -> Result<StoredMerkleTree<'a, S>, String> {
+        if initial_leaves.is_empty() {
+            return Err("Empty tree".to_string());
+        }
+        if store.len(0)? > 0 {
+            return Err("The store already holds a tree".to_string());
+        }
+        let mut upper_children = initial_leaves
+            .iter()
+            .map(|s| hash_fn(s))
+            .collect::<Vec<String>>();
+        let mut level = 0;
+
+        // Even a single leaf is paired with itself
+        while upper_children.len() > 1 || level == 0 {
+            let new_upper_children = upper_children
+                .chunks(2)
+                .map(|chunk| {
+                    // "Balance" the tree -> Duplicate if there is no right leaf
+                    let right = chunk.last().unwrap();
+                    hash_fn(&format!("{}{}", chunk[0], right))
+                })
+                .collect::<Vec<String>>();
+
+            store.put_batch(
+                upper_children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, hash)| (level, index, hash))
+                    .collect(),
+            )?;
+            upper_children = new_upper_children;
+            level += 1;
+        }
+        store.put(level, 0, upper_children.pop().unwrap())?;
+
+        Ok(StoredMerkleTree { store, hash_fn })
+    }
+
+    // Uses the nodes already in the store, e.g. a FileStore written by a previous process.
+    pub fn open(store: S, hash_fn: &'a dyn Fn(&str) -> String) -> StoredMerkleTree<'a, S> {
+        StoredMerkleTree { store, hash_fn }
+    }
+
+    pub fn leaves_count(&self) -> Result<usize, String> {
+        self.store.len(0)
+    }
+
+    // Index of the root level
+    pub fn depth(&self) -> Result<usize, String> {
+        if self.store.len(0)? == 0 {
+            return Err("Empty tree".to_string());
+        }
+        // Even a single leaf has a level above it
+        let mut level = 1;
+        while self.store.len(level)? > 1 {
+            level += 1;
+        }
+        Ok(level)
+    }
+
+    pub fn root(&self) -> Result<String, String> {
+        self.node(self.depth()?, 0)
+    }
+
+    pub fn get_proof_by_index(&self, index: usize) -> Result<Vec<String>, String> {
+        if index >= self.leaves_count()? {
+            return Err("Index out of bounds".to_string());
+        }
+        let mut proof = Vec::new();
+        let mut index = index;
+
+        for level in 0..self.depth()? {
+            // "Balance" the tree -> The last node of an odd level is its own sibling
+            let sibling = match self.store.get(level, index ^ 1)? {
+                Some(hash) => hash,
+                None => self.node(level, index)?,
+            };
+            proof.push(sibling);
+            index /= 2;
+        }
+        Ok(proof)
+    }
+
+    pub fn verify(&self, proof: &[String], item: &str, index: usize) -> Result<bool, String> {
+        Ok(crate::merkle_tree_vec::verify_proof(
+            &self.root()?,
+            proof,
+            item,
+            index,
+            self.hash_fn,
+        ))
+    }
+
+    fn node(&self, level: usize, index: usize) -> Result<String, String> {
+        self.store
+            .get(level, index)?
+            .ok_or("Node not found".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "merkletree_in_rust_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_memory_store_same_as_vec() {
+        for size in 1..=9 {
+            let leaves = (0..size).map(|i| i.to_string()).collect::<Vec<String>>();
+
+            let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+            let stree = StoredMerkleTree::build(MemoryStore::new(), &leaves, &keccak256).unwrap();
+
+            assert_eq!(stree.root().unwrap(), mtree.root);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = stree.get_proof_by_index(index).unwrap();
+                assert_eq!(proof, mtree.get_proof_by_index(index).unwrap());
+                assert!(stree.verify(&proof, leaf, index).unwrap());
+            }
+        }
+    }
+    #[test]
+    fn test_file_store_persists() {
+        let dir = temp_dir("file_store_persists");
+        let leaves = (0..7).map(|i| i.to_string()).collect::<Vec<String>>();
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        let store = FileStore::open(&dir).unwrap();
+        let stree = StoredMerkleTree::build(store, &leaves, &keccak256).unwrap();
+        assert_eq!(stree.root().unwrap(), mtree.root);
+        drop(stree);
+
+        let reopened = StoredMerkleTree::open(FileStore::open(&dir).unwrap(), &keccak256);
+        assert_eq!(reopened.root().unwrap(), mtree.root);
+        assert_eq!(reopened.leaves_count().unwrap(), 7);
+        assert_eq!(
+            reopened.get_proof_by_index(6).unwrap(),
+            mtree.get_proof_by_index(6).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_build_over_stored_tree() {
+        let dir = temp_dir("build_over_stored_tree");
+        let leaves = (0..8).map(|i| i.to_string()).collect::<Vec<String>>();
+        let stree =
+            StoredMerkleTree::build(FileStore::open(&dir).unwrap(), &leaves, &keccak256).unwrap();
+        let root = stree.root().unwrap();
+        drop(stree);
+
+        let result =
+            StoredMerkleTree::build(FileStore::open(&dir).unwrap(), &leaves[..3], &keccak256);
+        assert_eq!(
+            result.err(),
+            Some("The store already holds a tree".to_string())
+        );
+        // Left untouched
+        let reopened = StoredMerkleTree::open(FileStore::open(&dir).unwrap(), &keccak256);
+        assert_eq!(reopened.leaves_count().unwrap(), 8);
+        assert_eq!(reopened.root().unwrap(), root);
+
+        fs::remove_dir_all(&dir).unwrap();
+        let stree =
+            StoredMerkleTree::build(FileStore::open(&dir).unwrap(), &leaves[..3], &keccak256)
+                .unwrap();
+        assert_eq!(stree.leaves_count().unwrap(), 3);
+        assert_eq!(
+            stree.root().unwrap(),
+            MerkleTreeVec::new(&leaves[..3], &keccak256).root
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn test_file_store_get_put() {
+        let dir = temp_dir("file_store_get_put");
+        let mut store = FileStore::open(&dir).unwrap();
+
+        store
+            .put_batch(vec![
+                (0, 1, "bb".to_string()),
+                (0, 0, "aa".to_string()),
+                (1, 0, "cc".to_string()),
+            ])
+            .unwrap();
+        store.put(0, 1, "dd".to_string()).unwrap();
+
+        assert_eq!(store.len(0).unwrap(), 2);
+        assert_eq!(store.get(0, 1).unwrap(), Some("dd".to_string()));
+        assert_eq!(store.get(1, 0).unwrap(), Some("cc".to_string()));
+        assert_eq!(store.get(2, 0).unwrap(), None);
+        assert!(store.put(0, 2, "eee".to_string()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+// Helpers to hash the leaves and each level of a tree.
+// With the "parallel" feature, inputs of at least PARALLEL_THRESHOLD items are split across threads
+// with rayon; below it (or without the feature) the work stays sequential.
+// Both paths keep the order of the items, so the resulting roots are bit-identical.
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use alloc::vec::Vec;
+
+// Spawning tasks for a small level costs more than hashing it.
+pub const PARALLEL_THRESHOLD: usize = 1024;
+
+#[cfg(feature = "parallel")]
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
+{
+    if items.len() < PARALLEL_THRESHOLD {
+        items.iter().map(f).collect()
+    } else {
+        items.par_iter().map(f).collect()
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    F: Fn(&T) -> U,
+{
+    items.iter().map(f).collect()
+}
+
+// Applies f to every pair of items, the last chunk has a single item if the length is odd.
+#[cfg(feature = "parallel")]
+pub fn map_chunks<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&[T]) -> U + Sync + Send,
+{
+    if items.len() < PARALLEL_THRESHOLD {
+        items.chunks(2).map(f).collect()
+    } else {
+        items.par_chunks(2).map(f).collect()
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn map_chunks<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    F: Fn(&[T]) -> U,
+{
+    items.chunks(2).map(f).collect()
+}
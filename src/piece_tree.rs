@@ -0,0 +1,263 @@
+// BitTorrent v2 style integrity for large files: the file is split into fixed-size pieces, the
+// leaves of a MerkleTreeVec are the hashes of the pieces, padded with zero hashes up to a power
+// of two so no node is ever duplicated. Any downloaded piece is then checked against the root with
+// its proof. Files are read piece by piece, never loaded entirely.
+extern crate crypto;
+
+use std::io::{Read, Seek, SeekFrom};
+
+use self::crypto::digest::Digest;
+use crypto::sha3::Sha3;
+
+use crate::merkle_tree_vec::{keccak256, verify_proof, MerkleTreeVec};
+
+pub const DEFAULT_PIECE_SIZE: usize = 16 * 1024;
+
+// Leaf of the padding after the last piece
+pub const PADDING_LEAF: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+pub struct PieceTree {
+    pub piece_size: usize,
+    pub file_len: u64,
+    // Hashes of the pieces, without the padding
+    pub piece_hashes: Vec<String>,
+    pub tree: MerkleTreeVec<'static>,
+}
+
+// What a downloader has to know beforehand to check the pieces it receives.
+// Only built with PieceVerifier::new, which rejects a piece size of 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceVerifier {
+    root: String,
+    piece_size: usize,
+    file_len: u64,
+}
+
+pub fn hash_piece(piece: &[u8]) -> String {
+    let mut sha3 = Sha3::keccak256();
+    sha3.input(piece);
+    sha3.result_str()
+}
+
+// Fills buf unless the end of the reader comes first, returns the number of bytes read
+fn read_piece(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(filled)
+}
+
+impl PieceTree {
+    pub fn new(mut reader: impl Read, piece_size: usize) -> Result<PieceTree, String> {
+        if piece_size == 0 {
+            return Err("Piece size must be at least 1".to_string());
+        }
+        let mut buf = vec![0u8; piece_size];
+        let mut piece_hashes = Vec::new();
+        let mut file_len = 0u64;
+
+        loop {
+            let n = read_piece(&mut reader, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            piece_hashes.push(hash_piece(&buf[..n]));
+            file_len += n as u64;
+        }
+        if piece_hashes.is_empty() {
+            return Err("Empty file".to_string());
+        }
+
+        let mut leaves = piece_hashes.clone();
+        leaves.resize(
+            piece_hashes.len().next_power_of_two(),
+            PADDING_LEAF.to_string(),
+        );
+
+        Ok(PieceTree {
+            piece_size,
+            file_len,
+            piece_hashes,
+            tree: MerkleTreeVec::new(&leaves, &keccak256),
+        })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.tree.root
+    }
+
+    pub fn pieces_count(&self) -> usize {
+        self.piece_hashes.len()
+    }
+
+    pub fn verifier(&self) -> PieceVerifier {
+        PieceVerifier {
+            root: self.tree.root.clone(),
+            piece_size: self.piece_size,
+            file_len: self.file_len,
+        }
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<Vec<String>, String> {
+        if index >= self.pieces_count() {
+            return Err("Index out of bounds".to_string());
+        }
+        self.tree.get_proof_by_index(index)
+    }
+}
+
+impl PieceVerifier {
+    // From metadata received from a peer, which may not be trusted
+    pub fn new(root: String, piece_size: usize, file_len: u64) -> Result<PieceVerifier, String> {
+        if piece_size == 0 {
+            return Err("Piece size must be at least 1".to_string());
+        }
+        Ok(PieceVerifier {
+            root,
+            piece_size,
+            file_len,
+        })
+    }
+
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    pub fn piece_size(&self) -> usize {
+        self.piece_size
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    pub fn pieces_count(&self) -> usize {
+        self.file_len.div_ceil(self.piece_size as u64) as usize
+    }
+
+    // Length of the piece at index, only the last one can be shorter
+    pub fn piece_len(&self, index: usize) -> Result<usize, String> {
+        if index >= self.pieces_count() {
+            return Err("Index out of bounds".to_string());
+        }
+        let start = index as u64 * self.piece_size as u64;
+        Ok((self.file_len - start).min(self.piece_size as u64) as usize)
+    }
+
+    pub fn verify_piece(&self, index: usize, piece: &[u8], proof: &[String]) -> bool {
+        match self.piece_len(index) {
+            Ok(len) if len == piece.len() => {
+                verify_proof(&self.root, proof, &hash_piece(piece), index, &keccak256)
+            }
+            _ => false,
+        }
+    }
+
+    // Checks the pieces start..start + proofs.len() of a (partially) downloaded file.
+    pub fn verify_pieces<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        start: usize,
+        proofs: &[Vec<String>],
+    ) -> Result<bool, String> {
+        let offset = start as u64 * self.piece_size as u64;
+        reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| e.to_string())?;
+
+        let mut buf = vec![0u8; self.piece_size];
+        for (index, proof) in (start..).zip(proofs) {
+            let n = read_piece(reader, &mut buf[..self.piece_len(index)?])?;
+            if !self.verify_piece(index, &buf[..n], proof) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn file(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_new() {
+        let pieces = PieceTree::new(Cursor::new(file(10)), 4).unwrap();
+        assert_eq!(pieces.pieces_count(), 3);
+        assert_eq!(pieces.piece_hashes[2], hash_piece(&file(10)[8..]));
+
+        let leaves = [
+            hash_piece(&file(10)[..4]),
+            hash_piece(&file(10)[4..8]),
+            hash_piece(&file(10)[8..]),
+            PADDING_LEAF.to_string(),
+        ];
+        assert_eq!(pieces.root(), MerkleTreeVec::new(&leaves, &keccak256).root);
+
+        assert!(PieceTree::new(Cursor::new(Vec::new()), 4).is_err());
+        assert!(PieceTree::new(Cursor::new(file(10)), 0).is_err());
+    }
+    #[test]
+    fn test_verify_piece() {
+        let data = file(5 * 1024 + 7);
+        let pieces = PieceTree::new(Cursor::new(&data), 1024).unwrap();
+        let verifier = pieces.verifier();
+        assert_eq!(verifier.pieces_count(), 6);
+
+        for (index, piece) in data.chunks(1024).enumerate() {
+            let proof = pieces.get_proof(index).unwrap();
+            assert!(verifier.verify_piece(index, piece, &proof));
+            assert!(!verifier.verify_piece(index, &piece[1..], &proof));
+
+            let mut tampered = piece.to_vec();
+            tampered[0] ^= 1;
+            assert!(!verifier.verify_piece(index, &tampered, &proof));
+        }
+        assert!(pieces.get_proof(6).is_err());
+    }
+    #[test]
+    fn test_verifier_new() {
+        let data = file(10);
+        let pieces = PieceTree::new(Cursor::new(&data), 4).unwrap();
+        let root = pieces.root().to_string();
+
+        let verifier = PieceVerifier::new(root.clone(), 4, 10).unwrap();
+        assert_eq!(verifier, pieces.verifier());
+        assert!(verifier.verify_piece(2, &data[8..], &pieces.get_proof(2).unwrap()));
+
+        assert_eq!(
+            PieceVerifier::new(root, 0, 10),
+            Err("Piece size must be at least 1".to_string())
+        );
+    }
+    #[test]
+    fn test_verify_pieces() {
+        let data = file(DEFAULT_PIECE_SIZE * 3 + 100);
+        let pieces = PieceTree::new(Cursor::new(&data), DEFAULT_PIECE_SIZE).unwrap();
+        let verifier = pieces.verifier();
+        let proofs = (1..4)
+            .map(|i| pieces.get_proof(i).unwrap())
+            .collect::<Vec<Vec<String>>>();
+
+        let mut downloaded = Cursor::new(data.clone());
+        assert!(verifier.verify_pieces(&mut downloaded, 1, &proofs).unwrap());
+
+        // Corruption in the last piece of the range
+        let mut corrupted = data;
+        corrupted[DEFAULT_PIECE_SIZE * 3 + 5] ^= 1;
+        let mut downloaded = Cursor::new(corrupted);
+        assert!(!verifier.verify_pieces(&mut downloaded, 1, &proofs).unwrap());
+        assert!(!verifier.verify_pieces(&mut downloaded, 0, &proofs).unwrap());
+    }
+}
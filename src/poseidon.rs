@@ -0,0 +1,275 @@
+// Poseidon Merkle tree over the BN254 scalar field, compatible with circomlib.
+// Inside ZK circuits Keccak-256 costs far more constraints than Poseidon, so the circuits verify
+// membership with circomlib's Poseidon(2) over (left, right) at each level.
+//
+// To match the circuits (e.g. Semaphore or Tornado's MerkleTreeChecker), the tree has a fixed depth:
+// the leaves are field elements (not hashed again) and the missing leaves are zeros,
+// instead of duplicating the last node like MerkleTreeVec does. Only the non-empty part of each level
+// is stored: the subtrees of zeros have the same hash at a given height, computed once.
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use light_poseidon::{Poseidon, PoseidonHasher};
+use std::str::FromStr;
+
+use crate::hashers::constant_time_eq;
+
+// The circom parameters for 2 inputs always exist
+fn poseidon2_hasher() -> Poseidon<Fr> {
+    Poseidon::<Fr>::new_circom(2).unwrap()
+}
+
+fn hash_pair(poseidon: &mut Poseidon<Fr>, left: &Fr, right: &Fr) -> Fr {
+    poseidon.hash(&[*left, *right]).unwrap()
+}
+
+// Builds the hasher on each call, trees and proofs reuse one
+pub fn poseidon2(left: &Fr, right: &Fr) -> Fr {
+    hash_pair(&mut poseidon2_hasher(), left, right)
+}
+
+// Leaves are encoded as in circom inputs: the decimal representation of the field element.
+// Values bigger than the field modulus are rejected instead of being reduced.
+pub fn field_from_str(s: &str) -> Result<Fr, String> {
+    let value = Fr::from_str(s).map_err(|_| "Invalid field element".to_string())?;
+    if field_to_string(&value) != s {
+        return Err("Invalid field element".to_string());
+    }
+    Ok(value)
+}
+
+pub fn field_to_string(value: &Fr) -> String {
+    value.into_bigint().to_string()
+}
+
+pub struct PoseidonMerkleTree {
+    pub depth: usize,
+    // levels[0] holds the leaves and levels[depth] the root, if there are leaves. The nodes after
+    // the end of a level are zero subtrees.
+    levels: Vec<Vec<Fr>>,
+    // zeros[h] is the hash of a subtree of height h with only zero leaves
+    zeros: Vec<Fr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoseidonProof {
+    pub leaf: Fr,
+    pub path_elements: Vec<Fr>,
+    // 0 if the node of the path is a left child, 1 if it is a right child
+    pub path_indices: Vec<u8>,
+}
+
+impl PoseidonMerkleTree {
+    pub fn new(leaves: &[Fr], depth: usize) -> Result<PoseidonMerkleTree, String> {
+        if depth >= usize::BITS as usize || leaves.len() > 1 << depth {
+            return Err("Too many leaves for the depth".to_string());
+        }
+        let mut poseidon = poseidon2_hasher();
+
+        let mut zeros = vec![Fr::from(0u64)];
+        for height in 0..depth {
+            let zero = zeros[height];
+            zeros.push(hash_pair(&mut poseidon, &zero, &zero));
+        }
+
+        let mut levels = vec![leaves.to_vec()];
+        for height in 0..depth {
+            let upper = levels[height]
+                .chunks(2)
+                .map(|chunk| {
+                    let right = chunk.get(1).unwrap_or(&zeros[height]);
+                    hash_pair(&mut poseidon, &chunk[0], right)
+                })
+                .collect::<Vec<Fr>>();
+            levels.push(upper);
+        }
+
+        Ok(PoseidonMerkleTree {
+            depth,
+            levels,
+            zeros,
+        })
+    }
+
+    pub fn root(&self) -> Fr {
+        self.node(self.depth, 0)
+    }
+
+    fn node(&self, height: usize, index: usize) -> Fr {
+        match self.levels[height].get(index) {
+            Some(node) => *node,
+            None => self.zeros[height],
+        }
+    }
+
+    pub fn get_proof(&self, index: usize) -> Result<PoseidonProof, String> {
+        if index >= 1 << self.depth {
+            return Err("Index out of bounds".to_string());
+        }
+        let mut path_elements = Vec::new();
+        let mut path_indices = Vec::new();
+        let mut index_item = index;
+
+        for height in 0..self.depth {
+            path_elements.push(self.node(height, index_item ^ 1));
+            path_indices.push((index_item % 2) as u8);
+            index_item /= 2;
+        }
+
+        Ok(PoseidonProof {
+            leaf: self.node(0, index),
+            path_elements,
+            path_indices,
+        })
+    }
+}
+
+impl PoseidonProof {
+    // `depth` is the one of the tree of `root`, as fixed by the circuit: a shorter path would let
+    // an inner node pass for a leaf.
+    pub fn verify(&self, root: &Fr, depth: usize) -> bool {
+        if self.path_elements.len() != depth || self.path_indices.len() != depth {
+            return false;
+        }
+        let mut poseidon = poseidon2_hasher();
+        let mut valid = true;
+        let mut check_root = self.leaf;
+        for (element, index) in self.path_elements.iter().zip(&self.path_indices) {
+            // An index other than 0 or 1 makes the proof invalid, the walk still goes on
+            valid &= *index <= 1;
+            check_root = match index {
+                0 => hash_pair(&mut poseidon, &check_root, element),
+                _ => hash_pair(&mut poseidon, element, &check_root),
+            };
+        }
+        valid
+            & constant_time_eq(
+                &check_root.into_bigint().to_bytes_le(),
+                &root.into_bigint().to_bytes_le(),
+            )
+    }
+
+    // Input of the circuit as JSON, with the signal names used by circomlib based circuits.
+    pub fn to_circuit_input(&self) -> String {
+        let path_elements = self
+            .path_elements
+            .iter()
+            .map(|e| format!("\"{}\"", field_to_string(e)))
+            .collect::<Vec<String>>()
+            .join(",");
+        let path_indices = self
+            .path_indices
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        format!(
+            "{{\"leaf\":\"{}\",\"pathElements\":[{}],\"pathIndices\":[{}]}}",
+            field_to_string(&self.leaf),
+            path_elements,
+            path_indices
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fr(x: u64) -> Fr {
+        Fr::from(x)
+    }
+
+    #[test]
+    fn test_poseidon2_circomlib() {
+        // Known answers from circomlibjs: poseidon([1, 2]) and poseidon([0, 0])
+        assert_eq!(
+            field_to_string(&poseidon2(&fr(1), &fr(2))),
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+        );
+        assert_eq!(
+            field_to_string(&poseidon2(&fr(0), &fr(0))),
+            "14744269619966411208579211824598458697587494354926760081771325075741142829156"
+        );
+    }
+    #[test]
+    fn test_field_from_str() {
+        assert_eq!(field_from_str("42").unwrap(), fr(42));
+        assert_eq!(field_from_str("0").unwrap(), fr(0));
+        assert!(field_from_str("abc").is_err());
+        assert!(field_from_str("042").is_err());
+        // The field modulus
+        assert!(field_from_str(
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617"
+        )
+        .is_err());
+    }
+    #[test]
+    fn test_root_zero_padding() {
+        let ptree = PoseidonMerkleTree::new(&[fr(1), fr(2), fr(3)], 2).unwrap();
+
+        let cmp = poseidon2(&poseidon2(&fr(1), &fr(2)), &poseidon2(&fr(3), &fr(0)));
+        assert_eq!(ptree.root(), cmp);
+        assert!(PoseidonMerkleTree::new(&[fr(1), fr(2), fr(3)], 1).is_err());
+    }
+    #[test]
+    fn test_proof() {
+        let leaves = (1..=5).map(fr).collect::<Vec<Fr>>();
+        let ptree = PoseidonMerkleTree::new(&leaves, 3).unwrap();
+
+        let proof = ptree.get_proof(2).unwrap();
+        assert_eq!(proof.path_indices, vec![0, 1, 0]);
+        assert_eq!(proof.path_elements[0], fr(4));
+        assert_eq!(proof.path_elements[1], poseidon2(&fr(1), &fr(2)));
+        assert!(proof.verify(&ptree.root(), ptree.depth));
+
+        let mut wrong = proof.clone();
+        wrong.leaf = fr(6);
+        assert!(!wrong.verify(&ptree.root(), ptree.depth));
+        let mut wrong = proof.clone();
+        wrong.path_indices[1] = 3;
+        assert!(!wrong.verify(&ptree.root(), ptree.depth));
+
+        // The parent of leaves 3 and 4, with the path above it
+        let shortened = PoseidonProof {
+            leaf: poseidon2(&fr(3), &fr(4)),
+            path_elements: proof.path_elements[1..].to_vec(),
+            path_indices: proof.path_indices[1..].to_vec(),
+        };
+        assert!(!shortened.verify(&ptree.root(), ptree.depth));
+        assert!(shortened.verify(&ptree.root(), ptree.depth - 1));
+    }
+    #[test]
+    fn test_depth_20() {
+        let depth = 20;
+        let ptree = PoseidonMerkleTree::new(&[fr(1), fr(2), fr(3)], depth).unwrap();
+
+        let mut zero = fr(0);
+        let mut root = poseidon2(&poseidon2(&fr(1), &fr(2)), &poseidon2(&fr(3), &fr(0)));
+        for height in 0..depth {
+            if height >= 2 {
+                root = poseidon2(&root, &zero);
+            }
+            zero = poseidon2(&zero, &zero);
+        }
+        assert_eq!(ptree.root(), root);
+        assert_eq!(PoseidonMerkleTree::new(&[], depth).unwrap().root(), zero);
+
+        for index in [2, 3, (1 << depth) - 1] {
+            let proof = ptree.get_proof(index).unwrap();
+            assert_eq!(proof.path_elements.len(), depth);
+            assert!(proof.verify(&root, depth));
+        }
+        assert!(ptree.get_proof(1 << depth).is_err());
+    }
+    #[test]
+    fn test_to_circuit_input() {
+        let ptree = PoseidonMerkleTree::new(&[fr(1), fr(2)], 1).unwrap();
+        let proof = ptree.get_proof(1).unwrap();
+
+        assert_eq!(
+            proof.to_circuit_input(),
+            "{\"leaf\":\"2\",\"pathElements\":[\"1\"],\"pathIndices\":[1]}"
+        );
+    }
+}
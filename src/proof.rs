@@ -0,0 +1,358 @@
+// JSON representation of an inclusion proof, shared by the command-line tool and the server.
+// It carries everything needed to check it without the tree: the leaf, its index, the expected
+// root, the siblings of every level and the options the tree was built with.
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hashers;
+use crate::kary_merkle_tree::{verify_kary_proof, FillPolicy, KaryMerkleTree, KaryProof};
+use crate::merkle_tree_vec::{verify_consistency, MerkleTreeVec};
+
+// How the hashes (root and siblings) are written. The trees always work with hex strings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Hex,
+    // Hex with a "0x" prefix, as used by Ethereum tooling
+    #[serde(rename = "0x")]
+    PrefixedHex,
+    Base64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Proof {
+    pub leaf: String,
+    pub index: usize,
+    pub root: String,
+    // For each level, the other members of the group in order
+    pub siblings: Vec<Vec<String>>,
+    pub hasher: String,
+    pub odd: FillPolicy,
+    #[serde(default = "default_arity")]
+    pub arity: usize,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+// Proof that the tree with the first `from` leaves is a prefix of the tree with `to` leaves
+// (see MerkleTreeVec::get_consistency_proof). Always binary with duplicated odd nodes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConsistencyProof {
+    pub from: usize,
+    pub to: usize,
+    pub old_root: String,
+    pub new_root: String,
+    // The hash of leaf `from - 1`, then its siblings
+    pub proof: Vec<String>,
+    pub hasher: String,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+fn default_arity() -> usize {
+    2
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Result<Encoding, String> {
+        match name {
+            "hex" => Ok(Encoding::Hex),
+            "0x" => Ok(Encoding::PrefixedHex),
+            "base64" => Ok(Encoding::Base64),
+            _ => Err(format!("Unknown encoding: {name}")),
+        }
+    }
+
+    pub fn encode(&self, hex: &str) -> Result<String, String> {
+        match self {
+            Encoding::Hex => Ok(hex.to_string()),
+            Encoding::PrefixedHex => Ok(format!("0x{hex}")),
+            Encoding::Base64 => {
+                let bytes = hex_to_bytes(hex)?;
+                let mut encoded = String::new();
+                for chunk in bytes.chunks(3) {
+                    let n = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+                    for i in 0..4 {
+                        if i <= chunk.len() {
+                            let sextet = (n >> (18 - 6 * i)) & 0x3f;
+                            encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+                        } else {
+                            encoded.push('=');
+                        }
+                    }
+                }
+                Ok(encoded)
+            }
+        }
+    }
+
+    pub fn decode(&self, s: &str) -> Result<String, String> {
+        match self {
+            Encoding::Hex => Ok(s.to_string()),
+            Encoding::PrefixedHex => s
+                .strip_prefix("0x")
+                .map(|hex| hex.to_string())
+                .ok_or("Missing 0x prefix".to_string()),
+            Encoding::Base64 => {
+                let s = s.trim_end_matches('=');
+                let mut bytes = Vec::new();
+                let mut n = 0u32;
+                for (i, c) in s.bytes().enumerate() {
+                    let sextet = BASE64_ALPHABET
+                        .iter()
+                        .position(|a| *a == c)
+                        .ok_or("Invalid base64".to_string())?;
+                    n = n << 6 | sextet as u32;
+                    if i % 4 == 3 {
+                        bytes.extend_from_slice(&n.to_be_bytes()[1..]);
+                        n = 0;
+                    }
+                }
+                match s.len() % 4 {
+                    0 => {}
+                    2 => bytes.push((n >> 4) as u8),
+                    3 => bytes.extend_from_slice(&((n >> 2) as u16).to_be_bytes()),
+                    _ => return Err("Invalid base64".to_string()),
+                }
+                Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+            }
+        }
+    }
+}
+
+fn hex_to_bytes(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err("Invalid hex".to_string());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+impl Proof {
+    pub fn from_tree(
+        tree: &KaryMerkleTree,
+        index: usize,
+        hasher: &str,
+        encoding: Encoding,
+    ) -> Result<Proof, String> {
+        let kary_proof = tree.get_proof_by_index(index)?;
+        Proof::encoded(
+            &tree.initial_leaves[index],
+            &tree.root,
+            kary_proof,
+            hasher,
+            tree.fill,
+            tree.arity,
+            encoding,
+        )
+    }
+
+    pub fn from_vec_tree(
+        tree: &MerkleTreeVec,
+        index: usize,
+        hasher: &str,
+        encoding: Encoding,
+    ) -> Result<Proof, String> {
+        let siblings = tree
+            .get_proof_by_index(index)?
+            .into_iter()
+            .map(|h| vec![h])
+            .collect();
+        Proof::encoded(
+            &tree.initial_leaves[index],
+            &tree.root,
+            KaryProof { index, siblings },
+            hasher,
+            FillPolicy::DuplicateLast,
+            2,
+            encoding,
+        )
+    }
+
+    // From a proof with its raw root and siblings, e.g. built with kary_proof_from_levels
+    pub fn encoded(
+        leaf: &str,
+        root: &str,
+        kary_proof: KaryProof,
+        hasher: &str,
+        odd: FillPolicy,
+        arity: usize,
+        encoding: Encoding,
+    ) -> Result<Proof, String> {
+        let siblings = kary_proof
+            .siblings
+            .iter()
+            .map(|level| level.iter().map(|h| encoding.encode(h)).collect())
+            .collect::<Result<Vec<Vec<String>>, String>>()?;
+
+        Ok(Proof {
+            leaf: leaf.to_string(),
+            index: kary_proof.index,
+            root: encoding.encode(root)?,
+            siblings,
+            hasher: hasher.to_string(),
+            odd,
+            arity,
+            encoding,
+        })
+    }
+
+    // Checks the proof against its own root, compare `root` with a trusted one as well.
+    pub fn verify(&self) -> Result<bool, String> {
+        if self.arity < 2 {
+            return Err("Arity must be at least 2".to_string());
+        }
+        let hash_fn = hashers::by_name(&self.hasher)?;
+        let siblings = self
+            .siblings
+            .iter()
+            .map(|level| level.iter().map(|h| self.encoding.decode(h)).collect())
+            .collect::<Result<Vec<Vec<String>>, String>>()?;
+        let kary_proof = KaryProof {
+            index: self.index,
+            siblings,
+        };
+
+        Ok(verify_kary_proof(
+            &self.encoding.decode(&self.root)?,
+            &kary_proof,
+            &self.leaf,
+            self.arity,
+            &hash_fn,
+        ))
+    }
+
+    pub fn to_json(&self) -> String {
+        // A struct of strings and numbers always serializes
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(s: &str) -> Result<Proof, String> {
+        serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+impl ConsistencyProof {
+    // `tree` is the newer tree, with `to` leaves.
+    pub fn from_vec_tree(
+        tree: &MerkleTreeVec,
+        from: usize,
+        hasher: &str,
+        encoding: Encoding,
+    ) -> Result<ConsistencyProof, String> {
+        let proof = tree
+            .get_consistency_proof(from)?
+            .iter()
+            .map(|h| encoding.encode(h))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        Ok(ConsistencyProof {
+            from,
+            to: tree.initial_leaves.len(),
+            old_root: encoding.encode(&tree.root_of_first(from)?)?,
+            new_root: encoding.encode(&tree.root)?,
+            proof,
+            hasher: hasher.to_string(),
+            encoding,
+        })
+    }
+
+    // Checks the proof against its own roots, compare them with trusted ones as well.
+    pub fn verify(&self) -> Result<bool, String> {
+        let hash_fn = hashers::by_name(&self.hasher)?;
+        let proof = self
+            .proof
+            .iter()
+            .map(|h| self.encoding.decode(h))
+            .collect::<Result<Vec<String>, String>>()?;
+
+        Ok(verify_consistency(
+            &self.encoding.decode(&self.old_root)?,
+            &self.encoding.decode(&self.new_root)?,
+            self.from,
+            self.to,
+            &proof,
+            &hash_fn,
+        ))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(s: &str) -> Result<ConsistencyProof, String> {
+        serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::keccak256;
+    use crate::slice_to_string;
+
+    #[test]
+    fn test_encoding_roundtrip() {
+        let hex = keccak256("D");
+        for encoding in [Encoding::Hex, Encoding::PrefixedHex, Encoding::Base64] {
+            let encoded = encoding.encode(&hex).unwrap();
+            assert_eq!(encoding.decode(&encoded).unwrap(), hex);
+        }
+        // RFC 4648 test vectors
+        assert_eq!(Encoding::Base64.encode("666f6f").unwrap(), "Zm9v");
+        assert_eq!(Encoding::Base64.encode("666f6f62").unwrap(), "Zm9vYg==");
+        assert_eq!(Encoding::Base64.encode("666f6f6261").unwrap(), "Zm9vYmE=");
+        assert_eq!(Encoding::Base64.decode("Zm9vYmE=").unwrap(), "666f6f6261");
+    }
+    #[test]
+    fn test_json_roundtrip() {
+        let leaves = slice_to_string!["D", "E", "F"];
+        let ktree = KaryMerkleTree::new(&leaves, 2, FillPolicy::Truncate, &keccak256).unwrap();
+
+        let proof = Proof::from_tree(&ktree, 2, "keccak256", Encoding::Base64).unwrap();
+        let decoded = Proof::from_json(&proof.to_json()).unwrap();
+
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify().unwrap());
+    }
+    #[test]
+    fn test_from_vec_tree() {
+        let leaves = slice_to_string!["D", "E", "F", "G", "H"];
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        let mut proof = Proof::from_vec_tree(&mtree, 4, "keccak256", Encoding::Hex).unwrap();
+        assert_eq!(proof.root, mtree.root);
+        assert!(proof.verify().unwrap());
+
+        proof.leaf = "X".to_string();
+        assert!(!proof.verify().unwrap());
+    }
+    #[test]
+    fn test_consistency_json_roundtrip() {
+        let leaves = slice_to_string!["D", "E", "F", "G", "H"];
+        let mtree = MerkleTreeVec::new(&leaves, &keccak256);
+
+        let proof =
+            ConsistencyProof::from_vec_tree(&mtree, 3, "keccak256", Encoding::PrefixedHex).unwrap();
+        let decoded = ConsistencyProof::from_json(&proof.to_json()).unwrap();
+        assert_eq!(decoded, proof);
+        assert!(decoded.verify().unwrap());
+
+        let mut proof = decoded;
+        proof.old_root = proof.new_root.clone();
+        assert!(!proof.verify().unwrap());
+    }
+}
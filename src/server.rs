@@ -0,0 +1,530 @@
+// Small HTTP server around a MerkleTreeVec, so a frontend can fetch roots and proofs directly.
+// Every answer is JSON: proofs use the serialization of crate::proof, errors are {"error": "..."}.
+//
+//   GET  /root                     {"root", "leaves_count", "hasher", "encoding"}
+//   GET  /proof?leaf=X | ?index=N  Proof
+//   POST /leaves                   Appends a JSON array of leaves, answers like /root
+//   GET  /consistency?from=&to=    ConsistencyProof (`to` defaults to the current size)
+//
+// Bodies longer than MAX_BODY_LEN are refused with a 413.
+//
+// The levels of the tree are kept between requests and updated on appends. The tree of the first
+// `to` leaves only differs from them by the last node of each level, so answers about any prefix
+// hash O(log n) nodes.
+use std::io::Read;
+use std::net::SocketAddr;
+
+use serde_json::json;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::hashers;
+use crate::kary_merkle_tree::{FillPolicy, KaryProof};
+use crate::proof::{ConsistencyProof, Encoding, Proof};
+
+pub const MAX_BODY_LEN: u64 = 1 << 20;
+
+pub struct ProofServer {
+    server: Server,
+    leaves: Vec<String>,
+    // levels[0] are the hashed leaves, the last level is the root
+    levels: Vec<Vec<String>>,
+    hasher: String,
+    hash_fn: fn(&str) -> String,
+    encoding: Encoding,
+}
+
+impl ProofServer {
+    pub fn bind(
+        addr: &str,
+        leaves: Vec<String>,
+        hasher: &str,
+        encoding: Encoding,
+    ) -> Result<ProofServer, String> {
+        let hash_fn = hashers::by_name(hasher)?;
+        let server = Server::http(addr).map_err(|e| e.to_string())?;
+
+        let mut proof_server = ProofServer {
+            server,
+            leaves: Vec::new(),
+            levels: Vec::new(),
+            hasher: hasher.to_string(),
+            hash_fn,
+            encoding,
+        };
+        proof_server.append(leaves);
+        Ok(proof_server)
+    }
+
+    pub fn leaves(&self) -> &[String] {
+        &self.leaves
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // Serves requests one after the other, until the listener fails.
+    pub fn run(&mut self) -> Result<(), String> {
+        loop {
+            self.handle_next()?;
+        }
+    }
+
+    // Only fails when no request can be received: a client hanging up before the answer is sent
+    // doesn't stop the server.
+    pub fn handle_next(&mut self) -> Result<(), String> {
+        let mut request = self.server.recv().map_err(|e| e.to_string())?;
+
+        let too_large = || Err((413, "Body too large".to_string()));
+        let response = match request.body_length() {
+            Some(len) if len as u64 > MAX_BODY_LEN => too_large(),
+            // Chunked bodies have no length, they are cut one byte after the limit
+            _ => {
+                let mut body = String::new();
+                match request
+                    .as_reader()
+                    .take(MAX_BODY_LEN + 1)
+                    .read_to_string(&mut body)
+                {
+                    Ok(len) if len as u64 > MAX_BODY_LEN => too_large(),
+                    Ok(_) => self.handle(request.method(), request.url(), &body),
+                    Err(e) => Err((400, e.to_string())),
+                }
+            }
+        };
+        let (status, json) = match response {
+            Ok(json) => (200, json),
+            Err((status, e)) => (status, json!({ "error": e }).to_string()),
+        };
+
+        // Static header, always valid
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(json)
+            .with_status_code(status)
+            .with_header(content_type);
+        // The client is gone, there is nobody to tell
+        let _ = request.respond(response);
+        Ok(())
+    }
+
+    fn handle(&mut self, method: &Method, url: &str, body: &str) -> Result<String, (u16, String)> {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let params = parse_query(query);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let bad_request = |e: String| (400, e);
+
+        match (method, path) {
+            (Method::Get, "/root") => self.root(),
+            (Method::Get, "/proof") => {
+                let tree = self.tree(self.leaves.len())?;
+                let index = match (param("index"), param("leaf")) {
+                    (Some(index), _) => parse_usize(index)?,
+                    (None, Some(leaf)) => self
+                        .leaves
+                        .iter()
+                        .position(|l| l == leaf)
+                        .ok_or((404, "Item not found".to_string()))?,
+                    (None, None) => return Err(bad_request("Missing leaf or index".to_string())),
+                };
+                if index >= self.leaves.len() {
+                    return Err((404, "Index out of bounds".to_string()));
+                }
+                let siblings = tree.proof(index).into_iter().map(|h| vec![h]).collect();
+                let proof = Proof::encoded(
+                    &self.leaves[index],
+                    &tree.root(),
+                    KaryProof { index, siblings },
+                    &self.hasher,
+                    FillPolicy::DuplicateLast,
+                    2,
+                    self.encoding,
+                )
+                .map_err(|e| (500, e))?;
+                Ok(proof.to_json())
+            }
+            (Method::Post, "/leaves") => {
+                let new_leaves: Vec<String> =
+                    serde_json::from_str(body).map_err(|e| bad_request(e.to_string()))?;
+                self.append(new_leaves);
+                self.root()
+            }
+            (Method::Get, "/consistency") => {
+                let from = parse_usize(param("from").ok_or((400, "Missing from".to_string()))?)?;
+                let to = match param("to") {
+                    Some(to) => parse_usize(to)?,
+                    None => self.leaves.len(),
+                };
+                if to > self.leaves.len() {
+                    return Err((404, "Index out of bounds".to_string()));
+                }
+                if from == 0 || from > to {
+                    return Err(bad_request("Index out of bounds".to_string()));
+                }
+                let tree = self.tree(to)?;
+                let encode = |h: &str| self.encoding.encode(h).map_err(|e| (500, e));
+                let mut proof = vec![encode(&self.levels[0][from - 1])?];
+                for sibling in tree.proof(from - 1) {
+                    proof.push(encode(&sibling)?);
+                }
+                let proof = ConsistencyProof {
+                    from,
+                    to,
+                    old_root: encode(&self.tree(from)?.root())?,
+                    new_root: encode(&tree.root())?,
+                    proof,
+                    hasher: self.hasher.clone(),
+                    encoding: self.encoding,
+                };
+                Ok(proof.to_json())
+            }
+            (_, "/root" | "/proof" | "/leaves" | "/consistency") => {
+                Err((405, "Method not allowed".to_string()))
+            }
+            _ => Err((404, "Not found".to_string())),
+        }
+    }
+
+    fn root(&self) -> Result<String, (u16, String)> {
+        let tree = self.tree(self.leaves.len())?;
+        let root = self.encoding.encode(&tree.root()).map_err(|e| (500, e))?;
+
+        Ok(json!({
+            "root": root,
+            "leaves_count": self.leaves.len(),
+            "hasher": self.hasher,
+            "encoding": self.encoding,
+        })
+        .to_string())
+    }
+
+    // Tree of the first `count` leaves
+    fn tree(&self, count: usize) -> Result<Prefix<'_>, (u16, String)> {
+        if count == 0 {
+            return Err((404, "Empty tree".to_string()));
+        }
+        Ok(Prefix::new(&self.levels, count, self.hash_fn))
+    }
+
+    // Only the parents of the new leaves, and of the last old one when it was duplicated, are
+    // hashed again
+    fn append(&mut self, new_leaves: Vec<String>) {
+        let mut changed = self.leaves.len();
+        if self.levels.is_empty() {
+            self.levels.push(Vec::new());
+        }
+        let hashed = new_leaves.iter().map(|leaf| (self.hash_fn)(leaf));
+        self.levels[0].extend(hashed);
+        self.leaves.extend(new_leaves);
+
+        let mut level = 0;
+        while level == 0 || self.levels[level].len() > 1 {
+            changed /= 2;
+            let parents: Vec<String> = self.levels[level][2 * changed..]
+                .chunks(2)
+                .map(|pair| (self.hash_fn)(&format!("{}{}", pair[0], pair[pair.len() - 1])))
+                .collect();
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::new());
+            }
+            let upper = &mut self.levels[level + 1];
+            upper.truncate(changed);
+            upper.extend(parents);
+            level += 1;
+        }
+    }
+}
+
+// The tree of the first leaves, read from the levels of a larger one: a node is the same in
+// both unless it is the last of its level.
+struct Prefix<'a> {
+    levels: &'a [Vec<String>],
+    // Number of nodes of each level
+    sizes: Vec<usize>,
+    // Last node of each level
+    edges: Vec<String>,
+}
+
+impl<'a> Prefix<'a> {
+    fn new(levels: &'a [Vec<String>], count: usize, hash_fn: fn(&str) -> String) -> Prefix<'a> {
+        let mut prefix = Prefix {
+            levels,
+            sizes: vec![count],
+            edges: vec![levels[0][count - 1].clone()],
+        };
+        // A single leaf still gets one level above it
+        while prefix.sizes.len() == 1 || prefix.sizes[prefix.sizes.len() - 1] > 1 {
+            let level = prefix.sizes.len() - 1;
+            let last = prefix.sizes[level].div_ceil(2) - 1;
+            let left = prefix.node(level, 2 * last);
+            let right = prefix.sibling(level, 2 * last);
+            prefix.edges.push(hash_fn(&format!("{left}{right}")));
+            prefix.sizes.push(last + 1);
+        }
+        prefix
+    }
+
+    fn node(&self, level: usize, index: usize) -> String {
+        if index + 1 == self.sizes[level] {
+            self.edges[level].clone()
+        } else {
+            self.levels[level][index].clone()
+        }
+    }
+
+    // The node itself when it is duplicated
+    fn sibling(&self, level: usize, index: usize) -> String {
+        if index ^ 1 < self.sizes[level] {
+            self.node(level, index ^ 1)
+        } else {
+            self.node(level, index)
+        }
+    }
+
+    fn root(&self) -> String {
+        self.edges[self.edges.len() - 1].clone()
+    }
+
+    fn proof(&self, index: usize) -> Vec<String> {
+        (0..self.sizes.len() - 1)
+            .map(|level| self.sibling(level, index >> level))
+            .collect()
+    }
+}
+
+fn parse_usize(s: &str) -> Result<usize, (u16, String)> {
+    s.parse::<usize>().map_err(|e| (400, e.to_string()))
+}
+
+// key=value pairs of an application/x-www-form-urlencoded query
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() && s.is_char_boundary(i + 3) => {
+                match u8::from_str_radix(&s[i + 1..i + 3], 16) {
+                    Ok(b) => {
+                        decoded.push(b);
+                        i += 2;
+                    }
+                    // Not an escape, kept as is
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::{keccak256, MerkleTreeVec};
+    use crate::slice_to_string;
+    use serde_json::Value;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    // Sends one request and returns the status code and the JSON body
+    fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b+c"), "a b c");
+        assert_eq!(percent_decode("%C3%A9"), "é");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+    #[test]
+    fn test_endpoints() {
+        let leaves = slice_to_string!["D", "E", "F"].to_vec();
+        let mut server =
+            ProofServer::bind("127.0.0.1:0", leaves, "keccak256", Encoding::Hex).unwrap();
+        let addr = server.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let requests = 8;
+            let handle = s.spawn(move || {
+                for _ in 0..requests {
+                    server.handle_next().unwrap();
+                }
+                server
+            });
+
+            let mtree = MerkleTreeVec::new(&slice_to_string!["D", "E", "F"], &keccak256);
+            let (status, root) = send(addr, "GET", "/root", "");
+            assert_eq!(status, 200);
+            assert_eq!(root["root"], mtree.root);
+            assert_eq!(root["leaves_count"], 3);
+
+            let (status, by_leaf) = send(addr, "GET", "/proof?leaf=F", "");
+            assert_eq!(status, 200);
+            let (_, by_index) = send(addr, "GET", "/proof?index=2", "");
+            assert_eq!(by_leaf, by_index);
+            let proof = Proof::from_json(&by_leaf.to_string()).unwrap();
+            assert_eq!(proof.root, mtree.root);
+            assert!(proof.verify().unwrap());
+
+            let (status, root) = send(addr, "POST", "/leaves", r#"["G", "H"]"#);
+            assert_eq!(status, 200);
+            let new_tree =
+                MerkleTreeVec::new(&slice_to_string!["D", "E", "F", "G", "H"], &keccak256);
+            assert_eq!(root["root"], new_tree.root);
+
+            let (status, consistency) = send(addr, "GET", "/consistency?from=3", "");
+            assert_eq!(status, 200);
+            let proof = ConsistencyProof::from_json(&consistency.to_string()).unwrap();
+            assert_eq!(proof.old_root, mtree.root);
+            assert_eq!(proof.new_root, new_tree.root);
+            assert!(proof.verify().unwrap());
+
+            let (status, error) = send(addr, "GET", "/proof?leaf=X", "");
+            assert_eq!(status, 404);
+            assert_eq!(error["error"], "Item not found");
+            let (status, _) = send(addr, "GET", "/consistency?from=2&to=9", "");
+            assert_eq!(status, 404);
+            let (status, _) = send(addr, "DELETE", "/leaves", "");
+            assert_eq!(status, 405);
+
+            let server = handle.join().unwrap();
+            assert_eq!(server.leaves().len(), 5);
+        });
+    }
+    #[test]
+    fn test_cached_levels() {
+        let leaves: Vec<String> = (0..11).map(|i| i.to_string()).collect();
+        let mut server = ProofServer::bind(
+            "127.0.0.1:0",
+            leaves[..1].to_vec(),
+            "keccak256",
+            Encoding::Hex,
+        )
+        .unwrap();
+
+        // Appends of one and several leaves, checked against trees built from scratch
+        for end in [2, 3, 6, 7, 11] {
+            let body = serde_json::to_string(&leaves[server.leaves().len()..end]).unwrap();
+            server.handle(&Method::Post, "/leaves", &body).unwrap();
+            let tree = MerkleTreeVec::new(&leaves[..end], &keccak256);
+            assert_eq!(server.levels, tree.levels());
+
+            for index in 0..end {
+                let url = format!("/proof?index={index}");
+                let proof = Proof::from_vec_tree(&tree, index, "keccak256", Encoding::Hex);
+                assert_eq!(
+                    server.handle(&Method::Get, &url, "").unwrap(),
+                    proof.unwrap().to_json()
+                );
+            }
+            for to in 1..=end {
+                let prefix = MerkleTreeVec::new(&leaves[..to], &keccak256);
+                for from in 1..=to {
+                    let url = format!("/consistency?from={from}&to={to}");
+                    let proof =
+                        ConsistencyProof::from_vec_tree(&prefix, from, "keccak256", Encoding::Hex);
+                    assert_eq!(
+                        server.handle(&Method::Get, &url, "").unwrap(),
+                        proof.unwrap().to_json()
+                    );
+                }
+            }
+        }
+        let error = server.handle(&Method::Get, "/consistency?from=0", "");
+        assert_eq!(error, Err((400, "Index out of bounds".to_string())));
+        let error = server.handle(&Method::Get, "/proof?index=11", "");
+        assert_eq!(error, Err((404, "Index out of bounds".to_string())));
+    }
+    #[test]
+    fn test_client_hangs_up() {
+        let leaves = slice_to_string!["D", "E", "F"].to_vec();
+        let mut server =
+            ProofServer::bind("127.0.0.1:0", leaves, "keccak256", Encoding::Hex).unwrap();
+        let addr = server.local_addr().unwrap();
+        let (closed_tx, closed_rx) = std::sync::mpsc::channel();
+
+        std::thread::scope(|s| {
+            let handle = s.spawn(move || {
+                server.handle_next().unwrap();
+                closed_rx.recv().unwrap();
+                // Answers a client that reset the connection
+                server.handle_next().unwrap();
+                server.handle_next().unwrap();
+            });
+
+            // Two requests, then the connection is closed with the first answer unread, which
+            // resets it
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let request = "GET /root HTTP/1.1\r\nHost: localhost\r\n\r\n";
+            write!(stream, "{request}{request}").unwrap();
+            stream.peek(&mut [0]).unwrap();
+            drop(stream);
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            closed_tx.send(()).unwrap();
+
+            let (status, _) = send(addr, "GET", "/root", "");
+            assert_eq!(status, 200);
+            handle.join().unwrap();
+        });
+    }
+    #[test]
+    fn test_body_too_large() {
+        let leaves = slice_to_string!["D"].to_vec();
+        let mut server =
+            ProofServer::bind("127.0.0.1:0", leaves, "keccak256", Encoding::Hex).unwrap();
+        let addr = server.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let handle = s.spawn(move || {
+                for _ in 0..2 {
+                    server.handle_next().unwrap();
+                }
+                server
+            });
+
+            let leaf = "x".repeat(MAX_BODY_LEN as usize);
+            let (status, error) = send(addr, "POST", "/leaves", &format!(r#"["{leaf}"]"#));
+            assert_eq!(status, 413);
+            assert_eq!(error["error"], "Body too large");
+            let (status, _) = send(addr, "POST", "/leaves", r#"["E"]"#);
+            assert_eq!(status, 200);
+
+            let server = handle.join().unwrap();
+            assert_eq!(server.leaves(), ["D", "E"]);
+        });
+    }
+}
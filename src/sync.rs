@@ -0,0 +1,357 @@
+// Anti-entropy repair between two replicas holding trees over the same key space
+// (the same number of leaves, each index is a key), as done by Dynamo or Cassandra.
+// The replica that pulls compares roots, then asks for the children of the mismatching nodes one
+// level at a time, and finally transfers only the leaves that differ. After a pull both roots match.
+//
+// Messages are JSON, one per line, over any Read + Write transport (TCP, Unix sockets...).
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::merkle_tree_vec::MerkleTreeVec;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    Root,
+    // Hashes of the nodes at the level
+    Hashes { level: usize, indices: Vec<usize> },
+    Leaves { indices: Vec<usize> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    Root { leaves_count: usize, hash: String },
+    Hashes(Vec<String>),
+    Leaves(Vec<String>),
+    Error(String),
+}
+
+// The leaves and every level of their tree, so a changed leaf only rehashes its path.
+pub struct Replica<'a> {
+    pub leaves: Vec<String>,
+    levels: Vec<Vec<String>>,
+    hash_fn: &'a dyn Fn(&str) -> String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncStats {
+    pub round_trips: usize,
+    pub hashes_received: usize,
+    // Indices of the leaves that were repaired
+    pub repaired: Vec<usize>,
+}
+
+impl<'a> Replica<'a> {
+    pub fn new(leaves: Vec<String>, hash_fn: &'a dyn Fn(&str) -> String) -> Replica<'a> {
+        let levels = MerkleTreeVec::new(&leaves, hash_fn).levels();
+        Replica {
+            leaves,
+            levels,
+            hash_fn,
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        &self.levels.last().unwrap()[0]
+    }
+
+    pub fn set(&mut self, index: usize, value: String) -> Result<(), String> {
+        if index >= self.leaves.len() {
+            return Err("Index out of bounds".to_string());
+        }
+        self.levels[0][index] = (self.hash_fn)(&value);
+        self.leaves[index] = value;
+
+        let mut index_item = index;
+        for level in 0..self.levels.len() - 1 {
+            let parent = index_item / 2;
+            let left = &self.levels[level][2 * parent];
+            // "Balance" the tree -> Duplicate if there is no right leaf
+            let right = self.levels[level].get(2 * parent + 1).unwrap_or(left);
+            self.levels[level + 1][parent] = (self.hash_fn)(&format!("{}{}", left, right));
+            index_item = parent;
+        }
+        Ok(())
+    }
+
+    // Answers the requests of a pulling replica until the transport is closed.
+    pub fn serve<S: Read + Write>(&self, stream: S) -> Result<(), String> {
+        let mut stream = BufReader::new(stream);
+
+        while let Some(request) = read_message::<Request, S>(&mut stream)? {
+            let response = match request {
+                Request::Root => Response::Root {
+                    leaves_count: self.leaves.len(),
+                    hash: self.root().to_string(),
+                },
+                Request::Hashes { level, indices } => {
+                    match self
+                        .levels
+                        .get(level)
+                        .and_then(|nodes| collect(nodes, &indices))
+                    {
+                        Some(hashes) => Response::Hashes(hashes),
+                        None => Response::Error("Node not found".to_string()),
+                    }
+                }
+                Request::Leaves { indices } => match collect(&self.leaves, &indices) {
+                    Some(leaves) => Response::Leaves(leaves),
+                    None => Response::Error("Item not found".to_string()),
+                },
+            };
+            write_message(stream.get_mut(), &response)?;
+        }
+        Ok(())
+    }
+
+    // Repairs self from the replica served at the other end of the transport.
+    pub fn pull<S: Read + Write>(&mut self, stream: S) -> Result<SyncStats, String> {
+        let mut stream = BufReader::new(stream);
+        let mut stats = SyncStats::default();
+
+        let (leaves_count, root) = match request(&mut stream, &Request::Root, &mut stats)? {
+            Response::Root { leaves_count, hash } => (leaves_count, hash),
+            response => return Err(format!("Unexpected response: {:?}", response)),
+        };
+        if leaves_count != self.leaves.len() {
+            return Err("Different key spaces".to_string());
+        }
+        if root == self.root() {
+            return Ok(stats);
+        }
+
+        // Mismatching nodes of the current level, starting from the root
+        let mut mismatching = vec![0];
+        for level in (0..self.levels.len() - 1).rev() {
+            let indices = mismatching
+                .iter()
+                .flat_map(|i| [2 * i, 2 * i + 1])
+                // Duplicated odd nodes are not stored
+                .filter(|i| *i < self.levels[level].len())
+                .collect::<Vec<usize>>();
+
+            let request_hashes = Request::Hashes {
+                level,
+                indices: indices.clone(),
+            };
+            let hashes = match request(&mut stream, &request_hashes, &mut stats)? {
+                Response::Hashes(hashes) if hashes.len() == indices.len() => hashes,
+                response => return Err(format!("Unexpected response: {:?}", response)),
+            };
+            stats.hashes_received += hashes.len();
+
+            mismatching = indices
+                .into_iter()
+                .zip(hashes)
+                .filter(|(i, hash)| self.levels[level][*i] != *hash)
+                .map(|(i, _)| i)
+                .collect();
+        }
+
+        let request_leaves = Request::Leaves {
+            indices: mismatching.clone(),
+        };
+        let leaves = match request(&mut stream, &request_leaves, &mut stats)? {
+            Response::Leaves(leaves) if leaves.len() == mismatching.len() => leaves,
+            response => return Err(format!("Unexpected response: {:?}", response)),
+        };
+        // Nothing is applied unless the received leaves give the root of the peer
+        let changes: Vec<(usize, String)> = mismatching.iter().copied().zip(leaves).collect();
+        if self.staged_root(&changes) != root {
+            return Err("Roots still differ".to_string());
+        }
+        for (index, leaf) in changes {
+            self.set(index, leaf)?;
+        }
+        stats.repaired = mismatching;
+        Ok(stats)
+    }
+
+    // Root the tree would have with the leaves at the indices replaced, without changing it
+    fn staged_root(&self, changes: &[(usize, String)]) -> String {
+        let mut staged: BTreeMap<usize, String> = changes
+            .iter()
+            .map(|(index, leaf)| (*index, (self.hash_fn)(leaf)))
+            .collect();
+
+        for level in 0..self.levels.len() - 1 {
+            let nodes = &self.levels[level];
+            let node = |i: usize| staged.get(&i).unwrap_or(&nodes[i]);
+            let parents: BTreeSet<usize> = staged.keys().map(|i| i / 2).collect();
+            staged = parents
+                .into_iter()
+                .map(|parent| {
+                    let left = node(2 * parent);
+                    // "Balance" the tree -> Duplicate if there is no right leaf
+                    let right = if 2 * parent + 1 < nodes.len() {
+                        node(2 * parent + 1)
+                    } else {
+                        left
+                    };
+                    (parent, (self.hash_fn)(&format!("{}{}", left, right)))
+                })
+                .collect();
+        }
+        staged.remove(&0).unwrap_or_else(|| self.root().to_string())
+    }
+}
+
+fn collect(items: &[String], indices: &[usize]) -> Option<Vec<String>> {
+    indices.iter().map(|i| items.get(*i).cloned()).collect()
+}
+
+fn request<S: Read + Write>(
+    stream: &mut BufReader<S>,
+    request: &Request,
+    stats: &mut SyncStats,
+) -> Result<Response, String> {
+    write_message(stream.get_mut(), request)?;
+    stats.round_trips += 1;
+    match read_message(stream)? {
+        Some(Response::Error(e)) => Err(e),
+        Some(response) => Ok(response),
+        None => Err("Connection closed".to_string()),
+    }
+}
+
+fn write_message<T: Serialize>(stream: &mut impl Write, message: &T) -> Result<(), String> {
+    let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|e| e.to_string())?;
+    stream.flush().map_err(|e| e.to_string())
+}
+
+// None when the other end closed the transport
+fn read_message<T: for<'de> Deserialize<'de>, S: Read>(
+    stream: &mut BufReader<S>,
+) -> Result<Option<T>, String> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree_vec::keccak256;
+    use std::net::{TcpListener, TcpStream};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn test_set() {
+        let mut replica = Replica::new(leaves(7), &keccak256);
+        replica.set(6, "changed".to_string()).unwrap();
+
+        let mut changed = leaves(7);
+        changed[6] = "changed".to_string();
+        assert_eq!(
+            replica.root(),
+            MerkleTreeVec::new(&changed, &keccak256).root
+        );
+        assert!(replica.set(7, "X".to_string()).is_err());
+    }
+    #[test]
+    fn test_pull_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut source = leaves(100);
+        source[7] = "changed".to_string();
+        source[99] = "changed".to_string();
+        let expected_root = MerkleTreeVec::new(&source, &keccak256).root;
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let server = Replica::new(source.clone(), &keccak256);
+                let (stream, _) = listener.accept().unwrap();
+                server.serve(stream).unwrap();
+            });
+
+            let mut replica = Replica::new(leaves(100), &keccak256);
+            let stats = replica.pull(TcpStream::connect(addr).unwrap()).unwrap();
+
+            assert_eq!(stats.repaired, vec![7, 99]);
+            assert_eq!(replica.root(), expected_root);
+            assert_eq!(replica.leaves, source);
+            // Root, one request per level below it and the leaves
+            assert_eq!(stats.round_trips, 1 + 7 + 1);
+            assert!(stats.hashes_received <= 2 * 2 * 7);
+        });
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_pull_unix_socket() {
+        use std::os::unix::net::UnixStream;
+
+        let (client, server_stream) = UnixStream::pair().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let server = Replica::new(leaves(5), &keccak256);
+                server.serve(server_stream).unwrap();
+            });
+
+            // Already in sync: only the roots are exchanged
+            let mut replica = Replica::new(leaves(5), &keccak256);
+            let stats = replica.pull(&client).unwrap();
+            assert_eq!(stats.round_trips, 1);
+            assert!(stats.repaired.is_empty());
+
+            replica.set(0, "changed".to_string()).unwrap();
+            let stats = replica.pull(&client).unwrap();
+            assert_eq!(stats.repaired, vec![0]);
+            assert_eq!(replica.leaves, leaves(5));
+
+            drop(client);
+        });
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_pull_lying_peer() {
+        let (client, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                // Sends leaves that do not match the hashes of its tree
+                let mut server = Replica::new(leaves(6), &keccak256);
+                server.set(3, "changed".to_string()).unwrap();
+                server.leaves[3] = "lie".to_string();
+                server.serve(server_stream).unwrap();
+            });
+
+            let mut replica = Replica::new(leaves(6), &keccak256);
+            let root = replica.root().to_string();
+            assert_eq!(replica.pull(&client), Err("Roots still differ".to_string()));
+            assert_eq!(replica.leaves, leaves(6));
+            assert_eq!(replica.root(), root);
+            assert_eq!(
+                replica.levels,
+                MerkleTreeVec::new(&leaves(6), &keccak256).levels()
+            );
+            drop(client);
+        });
+    }
+    #[cfg(unix)]
+    #[test]
+    fn test_pull_different_key_spaces() {
+        let (client, server_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let server = Replica::new(leaves(5), &keccak256);
+                server.serve(server_stream).unwrap();
+            });
+
+            let mut replica = Replica::new(leaves(6), &keccak256);
+            assert!(replica.pull(&client).is_err());
+            drop(client);
+        });
+    }
+}
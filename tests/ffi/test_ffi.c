@@ -0,0 +1,77 @@
+/* C side of the ffi tests: builds a tree through the C ABI, gets its root and proofs, and
+ * verifies them. Compiled by build.rs and called from the tests of src/ffi.rs. */
+#include <stdbool.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "merkletree.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                                   \
+    do {                                                                                   \
+        if (!(condition)) {                                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+            failures++;                                                                    \
+        }                                                                                  \
+    } while (0)
+
+/* Returns the number of failed checks */
+int run_ffi_tests(const char *expected_root) {
+    const char *values[] = {"D", "E", "F", "G", "H"};
+    const uint8_t *leaves[5];
+    size_t lens[5];
+    for (int i = 0; i < 5; i++) {
+        leaves[i] = (const uint8_t *)values[i];
+        lens[i] = strlen(values[i]);
+    }
+
+    MtTree *tree = NULL;
+    CHECK(mt_tree_new(leaves, lens, 5, NULL, &tree) == MT_STATUS_OK);
+    if (tree == NULL) {
+        return failures;
+    }
+    CHECK(mt_tree_leaves_count(tree) == 5);
+
+    char root[65];
+    size_t written = 0;
+    CHECK(mt_tree_root(tree, root, sizeof(root), &written) == MT_STATUS_OK);
+    CHECK(written == 64);
+    CHECK(strcmp(root, expected_root) == 0);
+
+    /* 5 leaves -> 3 levels of siblings */
+    char proof[3 * 64 + 1];
+    for (size_t index = 0; index < 5; index++) {
+        CHECK(mt_tree_proof(tree, index, proof, sizeof(proof), &written) == MT_STATUS_OK);
+        CHECK(written == 3 * 64);
+
+        bool valid = false;
+        CHECK(mt_verify_proof(root, leaves[index], lens[index], index, proof, NULL, &valid) ==
+              MT_STATUS_OK);
+        CHECK(valid);
+
+        /* Another leaf, or the right leaf at another index (but the last leaf, paired with its
+         * own copy, is on both sides) */
+        valid = true;
+        CHECK(mt_verify_proof(root, (const uint8_t *)"X", 1, index, proof, NULL, &valid) ==
+              MT_STATUS_OK);
+        CHECK(!valid);
+        if (index < 4) {
+            valid = true;
+            CHECK(mt_verify_proof(root, leaves[index], lens[index], index ^ 1, proof,
+                                  "keccak256", &valid) == MT_STATUS_OK);
+            CHECK(!valid);
+        }
+    }
+
+    /* Too small: the length needed is still reported */
+    char small[16];
+    CHECK(mt_tree_proof(tree, 0, small, sizeof(small), &written) == MT_STATUS_BUFFER_TOO_SMALL);
+    CHECK(written == 3 * 64);
+    CHECK(mt_tree_proof(tree, 5, proof, sizeof(proof), &written) ==
+          MT_STATUS_INDEX_OUT_OF_BOUNDS);
+
+    mt_tree_free(tree);
+    mt_tree_free(NULL);
+    return failures;
+}